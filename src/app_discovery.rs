@@ -18,7 +18,10 @@ pub fn discover_apps() -> Vec<String> {
         let dir_path = PathBuf::from(dir);
         let entries = match fs::read_dir(&dir_path) {
             Ok(e) => e,
-            Err(_) => continue,
+            Err(e) => {
+                debug!("skipping {}: {}", dir, e);
+                continue;
+            }
         };
         for entry in entries.flatten() {
             let path = entry.path();
@@ -40,5 +43,28 @@ pub fn discover_apps() -> Vec<String> {
         }
     }
     apps.sort();
+    debug!("discovered {} apps in PATH", apps.len());
     apps
 }
+
+/// Look up `name` in PATH like `execvp` would. Names containing a slash are
+/// checked as given.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let is_executable = |path: &PathBuf| {
+        path.is_file()
+            && path
+                .metadata()
+                .map(|m| m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+    };
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    let path_var = env::var("PATH").unwrap_or_default();
+    path_var
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .map(|dir| PathBuf::from(dir).join(name))
+        .find(is_executable)
+}
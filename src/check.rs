@@ -0,0 +1,184 @@
+use std::path::Path;
+
+use crate::app_discovery::find_executable;
+use crate::color::parse_color;
+use crate::config::{self, Config, Diagnostic};
+use crate::keys::Bindings;
+use crate::{shell, systemd, terminal, theme, ui};
+
+const POSITIONS: &[&str] = &["top", "center", "bottom"];
+const DPI_RANGE: std::ops::RangeInclusive<u16> = 48..=480;
+
+/// Check values that parse fine but can't work, as (key, message) pairs.
+pub fn validate(config: &Config) -> Vec<(String, String)> {
+    let mut problems = Vec::new();
+    let appearance = &config.appearance;
+
+    const COLOR_KEYS: [&str; 6] = [
+        "appearance.foreground",
+        "appearance.background",
+        "appearance.selection_fg",
+        "appearance.selection_bg",
+        "appearance.match_highlight",
+        "appearance.prompt_color",
+    ];
+    for (key, value) in COLOR_KEYS.into_iter().zip(appearance.color_overrides()) {
+        if let Some(value) = value.filter(|v| parse_color(v).is_none()) {
+            problems.push((
+                key,
+                format!(
+                    "invalid colour '{}', expected #rrggbb, rgb(), hsl() or a colour name",
+                    value
+                ),
+            ));
+        }
+    }
+    match theme::load(&appearance.theme) {
+        Ok(theme) => {
+            for (name, value) in theme.entries() {
+                if parse_color(value).is_none() {
+                    problems.push((
+                        "appearance.theme",
+                        format!(
+                            "theme '{}' has invalid {} '{}', expected #rrggbb, rgb(), hsl() or a colour name",
+                            appearance.theme, name, value
+                        ),
+                    ));
+                }
+            }
+        }
+        Err(e) => problems.push(("appearance.theme", e)),
+    }
+
+    if !POSITIONS.contains(&appearance.position.as_str()) {
+        problems.push((
+            "appearance.position",
+            format!(
+                "invalid position '{}', expected {}",
+                appearance.position,
+                POSITIONS.join(", ")
+            ),
+        ));
+    }
+    if ui::resolve_font_path(&appearance.font_family).is_none() {
+        problems.push((
+            "appearance.font_family",
+            format!("font '{}' not found by fc-match", appearance.font_family),
+        ));
+    }
+    if appearance.font_size == 0 {
+        problems.push((
+            "appearance.font_size",
+            "font size must be above 0".to_string(),
+        ));
+    }
+    if !DPI_RANGE.contains(&appearance.dpi) {
+        problems.push((
+            "appearance.dpi",
+            format!(
+                "dpi {} out of range {}..={}",
+                appearance.dpi,
+                DPI_RANGE.start(),
+                DPI_RANGE.end()
+            ),
+        ));
+    }
+
+    let launcher = &config.launcher;
+    let template = terminal::resolve(&launcher.terminal);
+    match shell::split(&template) {
+        Ok(words) => match words.first() {
+            Some(program) if !program_exists(program) => problems.push((
+                "launcher.terminal",
+                format!("terminal '{}' not found", program),
+            )),
+            Some(_) => {}
+            None => problems.push(("launcher.terminal", "no terminal configured".to_string())),
+        },
+        Err(e) => problems.push(("launcher.terminal", e.to_string())),
+    }
+    if !["close", "focus"].contains(&launcher.second_instance.as_str()) {
+        problems.push((
+            "launcher.second_instance",
+            format!(
+                "invalid value '{}', expected close or focus",
+                launcher.second_instance
+            ),
+        ));
+    }
+    problems.extend(systemd::check(&launcher.systemd));
+    let mut problems: Vec<(String, String)> = problems
+        .into_iter()
+        .map(|(key, message)| (key.to_string(), message))
+        .collect();
+    problems.extend(Bindings::new(&launcher.hold_key, &config.keys).1);
+    problems
+}
+
+fn program_exists(program: &str) -> bool {
+    if program.contains('/') {
+        Path::new(program).is_file()
+    } else {
+        find_executable(program).is_some()
+    }
+}
+
+/// `--check-config`: report every problem in the config files. Returns
+/// whether they are fine.
+pub fn check_config() -> bool {
+    let (layers, errors) = config::read_layers();
+    if layers.is_empty() && errors.is_empty() {
+        println!(
+            "{}: not found, defaults are used",
+            config::config_path().display()
+        );
+    }
+    for e in &errors {
+        eprintln!("{}", e);
+    }
+    if !errors.is_empty() {
+        eprintln!("Files with errors are ignored until they are fixed.");
+    }
+    let config = match config::merge_layers(&layers) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    let config = match config::apply_overrides(&config, config::cli_overrides()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    let problems = validate(&config);
+    for (key, message) in &problems {
+        if config::is_overridden(key) {
+            eprintln!("-o {}: {}", key, message);
+            continue;
+        }
+        // Blame the last file that sets the key, or the user's config
+        let (path, position) = layers
+            .iter()
+            .rev()
+            .find_map(|layer| Some((layer.path.clone(), config::locate(&layer.content, key)?)))
+            .map_or((config::config_path(), None), |(path, position)| {
+                (path, Some(position))
+            });
+        let diagnostic = Diagnostic {
+            position,
+            message: format!("{}: {}", key, message),
+        };
+        eprintln!("{}", diagnostic.at(&path));
+    }
+    if problems.is_empty() {
+        for layer in &layers {
+            println!("{}: ok", layer.path.display());
+        }
+    }
+    errors.is_empty() && problems.is_empty()
+}
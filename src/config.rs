@@ -1,24 +1,145 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::theme::{self, Theme};
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Config {
     pub appearance: Appearance,
     pub launcher: Launcher,
+    /// Key chords mapped to action names, on top of the default bindings
+    pub keys: BTreeMap<String, String>,
+    /// Why a config file was not used, when `load` skipped it
+    #[serde(skip)]
+    pub load_warning: Option<String>,
+    /// Files the config was merged from, in load order
+    #[serde(skip)]
+    pub sources: Vec<PathBuf>,
+}
+
+/// A problem in the config file, with its 1-based line and column if known.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub position: Option<(usize, usize)>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Format as `path:line:column: message`, the usual compiler style.
+    pub fn at(&self, path: &Path) -> String {
+        match self.position {
+            Some(_) => format!("{}:{}", path.display(), self),
+            None => format!("{}: {}", path.display(), self),
+        }
+    }
 }
 
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some((line, column)) => write!(f, "{}:{}: {}", line, column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+pub const DEFAULT_TERMINAL: &str = "x-terminal-emulator -e";
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Launcher {
     pub terminal: String,
+    /// Key chord that runs the selection in a terminal kept open afterwards;
+    /// the same as binding `accept-hold` in `[keys]`
+    pub hold_key: String,
+    pub opener: String,
+    /// What invoking the launcher while it is open does: "close" or "focus"
+    pub second_instance: String,
+    /// Match case-insensitively even when the query has capitals
+    pub ignore_case: bool,
+    pub translate_paths: bool,
+    pub translate_paths_exclude: Vec<String>,
+    pub import_login_env: bool,
+    /// Seconds before the captured login environment is refreshed
+    pub login_env_ttl: u64,
+    pub env: BTreeMap<String, String>,
+    /// Watch launched processes and notify when they die early
+    pub supervise: bool,
+    pub supervise_grace_ms: u64,
+    /// Command receiving title and body as arguments; empty uses notify-send
+    pub notify_command: String,
+    pub systemd: SystemdScope,
+    pub rules: Vec<LaunchRule>,
+}
+
+/// Run launched programs in transient `systemd-run --user --scope` units.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SystemdScope {
+    pub enabled: bool,
+    /// Memory limit such as "2G" or "50%"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_max: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_weight: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Platform {
+    Linux,
+    Windows,
+}
+
+/// A `[[launcher.rules]]` entry, applied to programs whose name matches.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LaunchRule {
+    /// Program name or glob, e.g. "htop" or "*.exe"
+    pub name: String,
+    /// Always run in a terminal, or with false never, even on Shift+Enter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub terminal: Option<bool>,
+    pub env: BTreeMap<String, String>,
+    /// Run in a terminal that stays open after the command exits
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hold: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    pub args_prefix: Vec<String>,
+    pub args_suffix: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub platform: Option<Platform>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translate_paths: Option<bool>,
 }
 
 impl Default for Launcher {
     fn default() -> Self {
         Self {
-            terminal: "x-terminal-emulator -e".to_string(),
+            terminal: DEFAULT_TERMINAL.to_string(),
+            hold_key: "ctrl+shift+enter".to_string(),
+            opener: String::new(),
+            second_instance: "close".to_string(),
+            ignore_case: false,
+            translate_paths: true,
+            translate_paths_exclude: Vec::new(),
+            import_login_env: false,
+            login_env_ttl: 3600,
+            env: BTreeMap::new(),
+            supervise: false,
+            supervise_grace_ms: 1000,
+            notify_command: String::new(),
+            systemd: SystemdScope::default(),
+            rules: Vec::new(),
         }
     }
 }
@@ -26,124 +147,505 @@ impl Default for Launcher {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Appearance {
-    pub foreground: String,
-    pub background: String,
-    pub selection_fg: String,
-    pub selection_bg: String,
-    pub match_highlight: String,
-    pub prompt_color: String,
+    /// Bundled preset or file in `themes/`; the colours below override it
+    pub theme: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub foreground: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub background: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub match_highlight: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_color: Option<String>,
     pub font_family: String,
     pub font_size: u8,
     pub dpi: u16,
     pub position: String,
+    /// Text shown before the query
+    pub prompt: String,
+    /// Show results as a vertical list of this many lines; 0 keeps one bar
+    pub lines: u16,
 }
 
 impl Default for Appearance {
     fn default() -> Self {
         Self {
-            foreground: "#f8f8f2".to_string(),
-            background: "#21222c".to_string(),
-            selection_fg: "#f8f8f2".to_string(),
-            selection_bg: "#6272a4".to_string(),
-            match_highlight: "#8be9fd".to_string(),
-            prompt_color: "#bd93f9".to_string(),
+            theme: theme::DEFAULT_THEME.to_string(),
+            foreground: None,
+            background: None,
+            selection_fg: None,
+            selection_bg: None,
+            match_highlight: None,
+            prompt_color: None,
             font_family: "Monospace".to_string(),
             font_size: 10,
             dpi: 96,
             position: "top".to_string(),
+            prompt: String::new(),
+            lines: 0,
         }
     }
 }
 
+impl Appearance {
+    /// Colour overrides in the order of `Theme::entries`.
+    pub fn color_overrides(&self) -> [Option<&str>; 6] {
+        [
+            self.foreground.as_deref(),
+            self.background.as_deref(),
+            self.selection_fg.as_deref(),
+            self.selection_bg.as_deref(),
+            self.match_highlight.as_deref(),
+            self.prompt_color.as_deref(),
+        ]
+    }
+
+    /// The colours to draw with: the theme's, with explicit keys on top.
+    /// An unknown theme falls back to the default one.
+    pub fn colors(&self) -> Theme {
+        let theme = theme::load(&self.theme).unwrap_or_else(|e| {
+            warn!("appearance.theme: {}", e);
+            Theme::default()
+        });
+        theme.with_overrides(self.color_overrides())
+    }
+}
+
 impl Config {
+    /// Load the config file. Errors are logged and remembered in
+    /// `load_warning`, and the defaults are used instead.
     pub fn load() -> Self {
-        let path = config_path();
-        if path.exists() {
-            if let Ok(content) = fs::read_to_string(&path) {
-                if let Ok(config) = toml::from_str(&content) {
-                    return config;
-                }
+        let config = Self::load_file();
+        let overrides = cli_overrides();
+        if overrides.is_empty() {
+            return config;
+        }
+        match apply_overrides(&config, overrides) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("{}", e);
+                config
             }
         }
-        Self::default()
+    }
+
+    fn load_file() -> Self {
+        let (layers, errors) = read_layers();
+        for e in &errors {
+            warn!("{}; file ignored", e);
+        }
+        if layers.is_empty() && errors.is_empty() {
+            debug!("no config at {}, using defaults", config_path().display());
+        }
+        let mut config = match merge_layers(&layers) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("{}; using defaults", e);
+                return Self {
+                    load_warning: Some(e),
+                    ..Self::default()
+                };
+            }
+        };
+        config.load_warning = errors.into_iter().next();
+        config
     }
 }
 
-pub fn config_dir() -> PathBuf {
-    dirs::config_dir()
-        .unwrap_or_else(|| PathBuf::from("."))
-        .join("ctrl-space-wsl")
+/// A config file that was read and parsed, without its `include` key.
+pub struct Layer {
+    pub path: PathBuf,
+    pub content: String,
+    table: toml::Table,
 }
 
-fn config_path() -> PathBuf {
-    config_dir().join("config.toml")
+/// Config files in load order, later ones overriding earlier ones: the
+/// system-wide ones in `$XDG_CONFIG_DIRS`, the user's, then the one named
+/// by `CTRL_SPACE_WSL_CONFIG`.
+fn layer_paths() -> Vec<PathBuf> {
+    let system_dirs = std::env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+    // The most important directory comes first, so it is loaded last
+    let mut paths: Vec<PathBuf> = system_dirs
+        .split(':')
+        .filter(|dir| !dir.is_empty())
+        .rev()
+        .map(|dir| Path::new(dir).join("ctrl-space-wsl").join("config.toml"))
+        .collect();
+    paths.push(config_path());
+    if let Some(path) = std::env::var_os("CTRL_SPACE_WSL_CONFIG").filter(|p| !p.is_empty()) {
+        paths.push(PathBuf::from(path));
+    }
+    paths
 }
 
-pub enum CreateConfigResult {
-    Created(PathBuf),
-    NeedsConfirmation(PathBuf),
+/// Read every config file with its includes, in load order. Files that
+/// can't be read or parsed are left out and reported as errors.
+pub fn read_layers() -> (Vec<Layer>, Vec<String>) {
+    let mut layers = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen = Vec::new();
+    let explicit = std::env::var_os("CTRL_SPACE_WSL_CONFIG").map(PathBuf::from);
+    for path in layer_paths() {
+        // Only a file asked for by name has to exist
+        if !path.exists() && explicit.as_ref() != Some(&path) {
+            continue;
+        }
+        read_layer(&path, &mut layers, &mut errors, &mut seen);
+    }
+    (layers, errors)
 }
 
-fn detect_dpi() -> u16 {
-    let output = std::process::Command::new("xdpyinfo").output().ok();
-    if let Some(output) = output {
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            for line in stdout.lines() {
-                if line.contains("resolution:") {
-                    if let Some(dpi_str) = line.split_whitespace().nth(1) {
-                        if let Some(dpi) = dpi_str.split('x').next() {
-                            if let Ok(dpi) = dpi.parse::<u16>() {
-                                return dpi;
-                            }
-                        }
-                    }
-                }
+/// Read `path`, after the files it includes so that it overrides them.
+fn read_layer(
+    path: &Path,
+    layers: &mut Vec<Layer>,
+    errors: &mut Vec<String>,
+    seen: &mut Vec<PathBuf>,
+) {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if seen.contains(&canonical) {
+        debug!("{} already loaded", path.display());
+        return;
+    }
+    seen.push(canonical);
+
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => {
+            errors.push(format!("cannot read {}: {}", path.display(), e));
+            return;
+        }
+    };
+    // Parsing into a Config too catches wrong types, with their location
+    let parsed = parse(&content).and_then(|_| {
+        toml::from_str::<toml::Table>(&content).map_err(|e| Diagnostic {
+            position: e.span().map(|span| line_col(&content, span.start)),
+            message: e.message().to_string(),
+        })
+    });
+    let mut table = match parsed {
+        Ok(table) => table,
+        Err(e) => {
+            errors.push(e.at(path));
+            return;
+        }
+    };
+
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(includes)) if includes.iter().all(toml::Value::is_str) => includes
+            .iter()
+            .filter_map(|v| v.as_str().map(str::to_string))
+            .collect(),
+        Some(_) => {
+            let diagnostic = Diagnostic {
+                position: locate(&content, "include"),
+                message: "include: expected an array of paths".to_string(),
+            };
+            errors.push(diagnostic.at(path));
+            return;
+        }
+    };
+    let home = std::env::var("HOME").unwrap_or_default();
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for include in includes {
+        // Relative includes are found next to the including file
+        let include = dir.join(crate::shell::expand_tilde(&include, &home));
+        if include.exists() {
+            read_layer(&include, layers, errors, seen);
+        } else {
+            errors.push(format!(
+                "{}: include {}: no such file",
+                path.display(),
+                include.display()
+            ));
+        }
+    }
+
+    debug!("loaded {}", path.display());
+    layers.push(Layer {
+        path: path.to_path_buf(),
+        content,
+        table,
+    });
+}
+
+/// Everything the layers set, merged, with no defaults filled in.
+pub fn layers_table<'a>(layers: impl IntoIterator<Item = &'a Layer>) -> toml::Table {
+    let mut table = toml::Table::new();
+    for layer in layers {
+        merge(&mut table, layer.table.clone());
+    }
+    table
+}
+
+/// Merge the layers into one config.
+pub fn merge_layers(layers: &[Layer]) -> Result<Config, String> {
+    let table = layers_table(layers);
+    let mut config = Config::deserialize(table).map_err(|e| e.message().to_string())?;
+    config.sources = layers.iter().map(|layer| layer.path.clone()).collect();
+    Ok(config)
+}
+
+/// Deep merge: tables are merged key by key, arrays are appended to, and
+/// anything else is replaced. Arrays of tables, like `[[launcher.rules]]`,
+/// are prepended instead: the first matching rule wins, so the later file's
+/// rules have to come first to override the earlier ones.
+fn merge(base: &mut toml::Table, top: toml::Table) {
+    for (key, value) in top {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(top)) => merge(base, top),
+            (Some(toml::Value::Array(base)), toml::Value::Array(mut top))
+                if top.iter().all(toml::Value::is_table) =>
+            {
+                top.append(base);
+                *base = top;
             }
+            (Some(toml::Value::Array(base)), toml::Value::Array(top)) => base.extend(top),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// A `-o section.key=value` override from the command line. The value is
+/// read as TOML, and taken as a plain string when it isn't valid TOML, so
+/// `-o appearance.position=bottom` needs no quotes.
+#[derive(Debug, Clone)]
+pub struct Override {
+    pub key: String,
+    value: toml::Value,
+}
+
+impl Override {
+    pub fn new(key: &str, value: toml::Value) -> Self {
+        Self {
+            key: key.to_string(),
+            value,
         }
     }
-    96
 }
 
-pub fn create_default_config(force: bool) -> std::io::Result<CreateConfigResult> {
-    let dir = config_dir();
-    fs::create_dir_all(&dir)?;
-    let path = dir.join("config.toml");
-    let mut config = Config::default();
-    config.appearance.dpi = detect_dpi();
-    let new_content = toml::to_string_pretty(&config).unwrap_or_default();
-    if path.exists() {
-        let existing = fs::read_to_string(&path).unwrap_or_default();
-        if existing != new_content && !force {
-            return Ok(CreateConfigResult::NeedsConfirmation(path));
+impl FromStr for Override {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid override '{}', expected section.key=value", s))?;
+        let key = key.trim();
+        if key.is_empty() || key.split('.').any(str::is_empty) {
+            return Err(format!("invalid key '{}' in override '{}'", key, s));
         }
+        let value = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .unwrap_or_else(|| toml::Value::String(value.to_string()));
+        Ok(Self {
+            key: key.to_string(),
+            value,
+        })
     }
-    fs::write(&path, new_content)?;
-    Ok(CreateConfigResult::Created(path))
 }
 
-pub fn confirm_overwrite() -> bool {
-    use std::io::{self, Write};
-    print!("Config file differs from default. Overwrite? [y/N] ");
-    io::stdout().flush().ok();
-    let mut input = String::new();
-    if io::stdin().read_line(&mut input).is_ok() {
-        let input = input.trim().to_lowercase();
-        return input == "y" || input == "yes";
+/// Config file and overrides given on the command line, set once at startup.
+struct Source {
+    path: Option<PathBuf>,
+    overrides: Vec<Override>,
+}
+
+static SOURCE: OnceLock<Source> = OnceLock::new();
+
+/// Use `path` instead of the default config file and apply `overrides` on
+/// top of it for every load. Overrides that don't fit the config are
+/// reported here rather than at each load.
+pub fn set_source(path: Option<PathBuf>, overrides: Vec<Override>) -> Result<(), String> {
+    if let Some(path) = &path {
+        if !path.is_file() {
+            return Err(format!("{}: no such config file", path.display()));
+        }
+    }
+    for o in &overrides {
+        apply_overrides(&Config::default(), std::slice::from_ref(o))?;
     }
-    false
+    let _ = SOURCE.set(Source { path, overrides });
+    Ok(())
 }
 
-#[derive(Clone, Copy, Debug)]
-pub struct Rgb(pub u8, pub u8, pub u8);
+pub fn cli_overrides() -> &'static [Override] {
+    SOURCE.get().map_or(&[], |source| &source.overrides)
+}
 
-pub fn parse_hex_color(hex: &str) -> Option<Rgb> {
-    let hex = hex.strip_prefix('#')?;
-    if hex.len() != 6 {
-        return None;
+/// Whether `key` (e.g. "appearance.position") was set with `-o`.
+pub fn is_overridden(key: &str) -> bool {
+    cli_overrides().iter().any(|o| o.key == key)
+}
+
+/// Apply overrides to a config by way of its TOML representation, so they
+/// go through the same deserialization as the file.
+pub fn apply_overrides(config: &Config, overrides: &[Override]) -> Result<Config, String> {
+    let mut table = toml::Table::try_from(config).map_err(|e| e.to_string())?;
+    for o in overrides {
+        let mut keys: Vec<&str> = o.key.split('.').collect();
+        let last = keys.pop().unwrap_or_default();
+        let mut current = &mut table;
+        for key in keys {
+            current = current
+                .entry(key)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                .as_table_mut()
+                .ok_or_else(|| format!("-o {}: '{}' is not a section", o.key, key))?;
+        }
+        current.insert(last.to_string(), o.value.clone());
     }
-    let r = u8::from_str_radix(&hex[0..2], 16).ok()?;
-    let g = u8::from_str_radix(&hex[2..4], 16).ok()?;
-    let b = u8::from_str_radix(&hex[4..6], 16).ok()?;
-    Some(Rgb(r, g, b))
+    let overridden = Config::deserialize(table).map_err(|e| match overrides {
+        [o] => format!("-o {}: {}", o.key, e.message()),
+        _ => format!("-o: {}", e.message()),
+    })?;
+    // What isn't serialized comes from the files, not the overrides
+    Ok(Config {
+        load_warning: config.load_warning.clone(),
+        sources: config.sources.clone(),
+        ..overridden
+    })
+}
+
+/// Parse config file contents, locating the error on failure.
+pub fn parse(content: &str) -> Result<Config, Diagnostic> {
+    toml::from_str(content).map_err(|e: toml::de::Error| Diagnostic {
+        position: e.span().map(|span| line_col(content, span.start)),
+        message: e.message().to_string(),
+    })
+}
+
+fn line_col(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or("").chars().count() + 1;
+    (line, column)
+}
+
+/// Find where `key` (e.g. "appearance.font_size") is set in `content`:
+/// the line and column of its value.
+pub fn locate(content: &str, key: &str) -> Option<(usize, usize)> {
+    let (section, name) = key.rsplit_once('.').unwrap_or(("", key));
+    let mut current = String::new();
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            current = header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_string();
+            continue;
+        }
+        if current != section {
+            continue;
+        }
+        let Some((lhs, rhs)) = trimmed.split_once('=') else {
+            continue;
+        };
+        if lhs.trim().trim_matches('"') == name {
+            let value_start = line.len() - rhs.trim_start().len();
+            return Some((i + 1, line[..value_start].chars().count() + 1));
+        }
+    }
+    None
+}
+
+pub fn config_dir() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("ctrl-space-wsl")
+}
+
+/// Directory for logs, history and other state that isn't configuration.
+pub fn state_dir() -> PathBuf {
+    dirs::state_dir()
+        .map(|dir| dir.join("ctrl-space-wsl"))
+        .unwrap_or_else(config_dir)
+}
+
+/// Directory for files that can be recreated, like the font lookup.
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .map(|dir| dir.join("ctrl-space-wsl"))
+        .unwrap_or_else(state_dir)
+}
+
+/// Directory for files that only mean something while we run, like the pid.
+pub fn runtime_dir() -> PathBuf {
+    dirs::runtime_dir()
+        .map(|dir| dir.join("ctrl-space-wsl"))
+        .unwrap_or_else(state_dir)
+}
+
+/// Move files that older versions kept next to the config to where they
+/// belong now. Files already at the new place win; a stale pid file is
+/// just removed.
+pub fn migrate_files() {
+    let old_dir = config_dir();
+    let _ = fs::remove_file(old_dir.join("pid"));
+
+    // The log is likely written at the new place already: put the old lines
+    // in front of it
+    let old_log = old_dir.join("ctrl-space-wsl.log");
+    let new_log = crate::log::log_path();
+    if old_log != new_log {
+        if let Ok(mut content) = fs::read(&old_log) {
+            content.extend(fs::read(&new_log).unwrap_or_default());
+            let moved = fs::create_dir_all(state_dir())
+                .and_then(|_| fs::write(&new_log, content))
+                .and_then(|_| fs::remove_file(&old_log));
+            match moved {
+                Ok(()) => info!("moved {} to {}", old_log.display(), new_log.display()),
+                Err(e) => warn!("cannot move {}: {}", old_log.display(), e),
+            }
+        }
+    }
+    for (name, new_dir) in [
+        ("freq.txt", state_dir()),
+        ("font_cache", cache_dir()),
+        ("login_env", cache_dir()),
+    ] {
+        let old = old_dir.join(name);
+        let new = new_dir.join(name);
+        if old == new || !old.exists() || new.exists() {
+            continue;
+        }
+        // Renaming fails across filesystems, copying doesn't
+        let moved = fs::create_dir_all(&new_dir).and_then(|_| {
+            fs::rename(&old, &new)
+                .or_else(|_| fs::copy(&old, &new).and_then(|_| fs::remove_file(&old)))
+        });
+        match moved {
+            Ok(()) => {
+                info!("moved {} to {}", old.display(), new.display());
+                // Older versions left the login environment readable by all
+                if name == "login_env" {
+                    use std::os::unix::fs::PermissionsExt;
+                    let _ = fs::set_permissions(&new, fs::Permissions::from_mode(0o600));
+                }
+            }
+            Err(e) => warn!("cannot move {} to {}: {}", old.display(), new.display(), e),
+        }
+    }
+}
+
+pub fn config_path() -> PathBuf {
+    SOURCE
+        .get()
+        .and_then(|source| source.path.clone())
+        .unwrap_or_else(|| config_dir().join("config.toml"))
 }
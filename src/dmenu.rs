@@ -0,0 +1,122 @@
+use std::io::{self, BufRead};
+use std::path::Path;
+
+use crate::config::{self, Config, Override};
+use crate::frequency::Frequency;
+use crate::ui;
+
+const USAGE: &str = "\
+usage: dmenu [-bfiv] [-l lines] [-p prompt] [-fn font] [-m monitor]
+             [-nb color] [-nf color] [-sb color] [-sf color] [-w windowid]";
+
+/// Whether to act as dmenu: run through a symlink named `dmenu`, or given
+/// `--dmenu`.
+pub fn is_invoked(args: &[String]) -> bool {
+    let argv0 = args.first().map(Path::new).and_then(Path::file_name);
+    argv0.is_some_and(|name| name == "dmenu") || args.iter().any(|a| a == "--dmenu")
+}
+
+/// Split an Xft font name like `monospace:size=10` or `DejaVu Sans Mono-12`
+/// into a family and a size.
+fn parse_font(font: &str) -> (String, Option<u8>) {
+    let mut parts = font.split(':');
+    let family = parts.next().unwrap_or_default();
+    let size = parts
+        .filter_map(|p| p.strip_prefix("pixelsize=").or(p.strip_prefix("size=")))
+        .find_map(|s| s.parse::<f32>().ok());
+    if let Some(size) = size {
+        return (family.to_string(), Some(size.round() as u8));
+    }
+    let sized = family
+        .rsplit_once('-')
+        .and_then(|(name, size)| Some((name, size.parse::<f32>().ok()?)));
+    match sized {
+        Some((name, size)) => (name.to_string(), Some(size.round() as u8)),
+        None => (family.to_string(), None),
+    }
+}
+
+/// Turn dmenu's flags into config overrides.
+fn overrides(args: &[String]) -> Result<Vec<Override>, String> {
+    let mut overrides = Vec::new();
+    let mut args = args.iter().filter(|a| *a != "--dmenu");
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "-b" => overrides.push(Override::new("appearance.position", "bottom".into())),
+            "-i" => overrides.push(Override::new("launcher.ignore_case", true.into())),
+            // Grabbing the keyboard first is what we always do
+            "-f" => {}
+            "-v" => {
+                println!("dmenu (ctrl-space-wsl {})", env!("CARGO_PKG_VERSION"));
+                std::process::exit(0);
+            }
+            "-l" | "-p" | "-fn" | "-m" | "-w" | "-nb" | "-nf" | "-sb" | "-sf" => {
+                let value = args.next().ok_or_else(|| USAGE.to_string())?;
+                let (key, value): (&str, toml::Value) = match flag.as_str() {
+                    "-l" => {
+                        let lines: u16 = value.parse().map_err(|_| USAGE.to_string())?;
+                        ("appearance.lines", i64::from(lines).into())
+                    }
+                    "-p" => ("appearance.prompt", value.as_str().into()),
+                    "-fn" => {
+                        let (family, size) = parse_font(value);
+                        if let Some(size) = size.filter(|&s| s > 0) {
+                            overrides.push(Override::new(
+                                "appearance.font_size",
+                                i64::from(size).into(),
+                            ));
+                        }
+                        ("appearance.font_family", family.into())
+                    }
+                    "-nb" => ("appearance.background", value.as_str().into()),
+                    "-nf" => ("appearance.foreground", value.as_str().into()),
+                    "-sb" => ("appearance.selection_bg", value.as_str().into()),
+                    "-sf" => ("appearance.selection_fg", value.as_str().into()),
+                    // The window always goes on the monitor with the pointer
+                    _ => continue,
+                };
+                overrides.push(Override::new(key, value));
+            }
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(overrides)
+}
+
+/// Run as dmenu: read items from stdin, print the selection. Returns the
+/// exit code, 1 when cancelled.
+pub fn run(args: &[String]) -> i32 {
+    let overrides = match overrides(&args[1..]) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        }
+    };
+    if let Err(e) = config::set_source(None, overrides) {
+        eprintln!("{}", e);
+        return 1;
+    }
+
+    let items: Vec<String> = io::stdin()
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .filter(|l| !l.is_empty())
+        .collect();
+    debug!("dmenu mode, {} items", items.len());
+
+    let config = Config::load();
+    match ui::run(config, Frequency::default(), items, true, None, false) {
+        Ok(Some(selection)) => {
+            println!("{}", selection);
+            0
+        }
+        Ok(None) => 1,
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("dmenu: {}", e);
+            1
+        }
+    }
+}
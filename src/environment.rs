@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::{cache_dir, Config};
+
+const MARKER: &str = "__CTRL_SPACE_WSL_ENV__";
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long output is still read after the shell has exited.
+const DRAIN_TIME: Duration = Duration::from_millis(100);
+const POLL: Duration = Duration::from_millis(20);
+
+/// Variables describing the capturing shell itself rather than the session.
+const SKIPPED: &[&str] = &["_", "PWD", "OLDPWD", "SHLVL", "PS1", "PS2"];
+
+fn parse_env(data: &[u8]) -> BTreeMap<String, String> {
+    String::from_utf8_lossy(data)
+        .split('\0')
+        .filter_map(|entry| entry.split_once('='))
+        .filter(|(key, _)| !key.is_empty() && !SKIPPED.contains(key))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Run the user's shell as an interactive login shell and dump its
+/// environment. Output from rc files is skipped by printing a marker first.
+fn capture() -> Option<BTreeMap<String, String>> {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/bash".to_string());
+    let script = format!("printf '\\0{}\\0'; env -0", MARKER);
+    let mut child = Command::new(&shell)
+        .args(["-l", "-i", "-c", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    // Read on a thread that can be left behind: a job the rc files start in
+    // the background may keep stdout open long after the shell is gone
+    let mut stdout = child.stdout.take()?;
+    let (sender, chunks) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut buf = [0; 4096];
+        while let Ok(n @ 1..) = stdout.read(&mut buf) {
+            if sender.send(buf[..n].to_vec()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let deadline = Instant::now() + CAPTURE_TIMEOUT;
+    let mut data = Vec::new();
+    let mut exited = None;
+    loop {
+        match chunks.recv_timeout(POLL) {
+            Ok(chunk) => data.extend_from_slice(&chunk),
+            Err(RecvTimeoutError::Disconnected) if exited.is_some() => break,
+            Err(RecvTimeoutError::Disconnected) => std::thread::sleep(POLL),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if exited.is_none() && matches!(child.try_wait(), Ok(Some(_))) {
+            exited = Some(Instant::now());
+        }
+        match exited {
+            Some(at) if at.elapsed() >= DRAIN_TIME => break,
+            None if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+            _ => {}
+        }
+    }
+
+    let marker = format!("\0{}\0", MARKER);
+    let start = data
+        .windows(marker.len())
+        .position(|w| w == marker.as_bytes())?
+        + marker.len();
+    let vars = parse_env(&data[start..]);
+    (!vars.is_empty()).then_some(vars)
+}
+
+pub fn cache_path() -> PathBuf {
+    cache_dir().join("login_env")
+}
+
+/// The login shell environment, from the cache if it is younger than `ttl`.
+fn login_env(ttl: Duration) -> Option<BTreeMap<String, String>> {
+    let cache = cache_path();
+    let fresh = fs::metadata(&cache)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| SystemTime::now().duration_since(t).ok())
+        .is_some_and(|age| age < ttl);
+    if fresh {
+        if let Ok(data) = fs::read(&cache) {
+            debug!("using cached login environment");
+            return Some(parse_env(&data));
+        }
+    }
+
+    let Some(vars) = capture() else {
+        warn!("failed to capture the login shell environment");
+        return None;
+    };
+    info!("captured {} variables from the login shell", vars.len());
+    let mut data = Vec::new();
+    for (key, value) in &vars {
+        data.extend_from_slice(key.as_bytes());
+        data.push(b'=');
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+    }
+    if let Err(e) = write_private(&cache, &data) {
+        warn!("cannot write {}: {}", cache.display(), e);
+    }
+    Some(vars)
+}
+
+/// Write a file only we can read, as the environment may hold tokens. A
+/// file left readable by an older version is tightened too.
+fn write_private(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(data)
+}
+
+/// Expand `$VAR` and `${VAR}` references against the current environment.
+fn expand_vars(value: &str) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, tail) = if let Some(braced) = after.strip_prefix('{') {
+            match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            }
+        } else {
+            let end = after
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(after.len());
+            (&after[..end], &after[end..])
+        };
+        if name.is_empty() {
+            out.push('$');
+        } else {
+            out.push_str(&env::var(name).unwrap_or_default());
+        }
+        rest = tail;
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Set up the environment inherited by discovery and every launched
+/// process: the login shell's variables if enabled, then the configured
+/// overlay. Must run before any other thread is started, apart from a
+/// capture's reader, which only reads its pipe.
+pub fn apply(config: &Config) {
+    if config.launcher.import_login_env {
+        let ttl = Duration::from_secs(config.launcher.login_env_ttl);
+        if let Some(vars) = login_env(ttl) {
+            for (key, value) in vars {
+                env::set_var(key, value);
+            }
+        }
+    }
+    for (key, value) in &config.launcher.env {
+        env::set_var(key, expand_vars(value));
+    }
+}
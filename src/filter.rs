@@ -148,11 +148,16 @@ fn match_fragmented(
     Some((total_score, all_indices))
 }
 
-pub fn filter_apps(apps: &[String], query: &str, frequency: &Frequency) -> Vec<FilteredApp> {
+pub fn filter_apps(
+    apps: &[String],
+    query: &str,
+    frequency: &Frequency,
+    ignore_case: bool,
+) -> Vec<FilteredApp> {
     let tokens: Vec<&str> = query.split_whitespace().collect();
     let query_joined: String = tokens.join(" ");
     // Smart-case: case-sensitive if query has any uppercase letter
-    let case_sensitive = query.chars().any(|c| c.is_ascii_uppercase());
+    let case_sensitive = !ignore_case && query.chars().any(|c| c.is_ascii_uppercase());
 
     let mut results: Vec<FilteredApp> = if tokens.is_empty() {
         apps.iter()
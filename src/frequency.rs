@@ -6,7 +6,7 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crate::app_discovery::discover_apps;
-use crate::config::config_dir;
+use crate::config::state_dir;
 
 pub struct Frequency {
     counts: HashMap<String, u32>,
@@ -30,7 +30,10 @@ impl Frequency {
         let counts = if path.exists() {
             let file = match File::open(&path) {
                 Ok(f) => f,
-                Err(_) => return Self::empty(path),
+                Err(e) => {
+                    warn!("cannot open {}: {}", path.display(), e);
+                    return Self::empty(path);
+                }
             };
             let reader = BufReader::new(file);
             let mut counts = HashMap::new();
@@ -41,6 +44,7 @@ impl Frequency {
                     }
                 }
             }
+            debug!("loaded {} history entries", counts.len());
             counts
         } else {
             HashMap::new()
@@ -93,6 +97,11 @@ impl Frequency {
         Ok(())
     }
 
+    /// Whether a background refresh finished since the last call.
+    pub fn take_refreshed(&self) -> bool {
+        self.dirty.swap(false, Ordering::Relaxed)
+    }
+
     pub fn refresh_in_background(&self) {
         let path = self.path.clone();
         let dirty = self.dirty.clone();
@@ -118,25 +127,30 @@ impl Frequency {
 
             // Only rewrite if we discover new apps
             if !has_new {
+                debug!("refresh: no new apps");
                 dirty.store(true, Ordering::Relaxed);
                 return;
             }
 
+            info!("refresh: found new apps, rewriting {}", path.display());
             if let Some(parent) = path.parent() {
                 let _ = fs::create_dir_all(parent);
             }
-            if let Ok(mut file) = File::create(&path) {
-                let mut entries: Vec<_> = counts.iter().collect();
-                entries.sort_by_key(|(name, _)| name.as_str());
-                for (name, count) in entries {
-                    let _ = writeln!(file, "{}\t{}", name, count);
+            match File::create(&path) {
+                Ok(mut file) => {
+                    let mut entries: Vec<_> = counts.iter().collect();
+                    entries.sort_by_key(|(name, _)| name.as_str());
+                    for (name, count) in entries {
+                        let _ = writeln!(file, "{}\t{}", name, count);
+                    }
                 }
+                Err(e) => warn!("cannot write {}: {}", path.display(), e),
             }
             dirty.store(true, Ordering::Relaxed);
         });
     }
 }
 
-fn data_path() -> std::path::PathBuf {
-    config_dir().join("freq.txt")
+pub fn data_path() -> std::path::PathBuf {
+    state_dir().join("freq.txt")
 }
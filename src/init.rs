@@ -0,0 +1,215 @@
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::config::{self, Config};
+
+fn detect_dpi() -> u16 {
+    let output = std::process::Command::new("xdpyinfo").output().ok();
+    if let Some(output) = output {
+        if output.status.success() {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            for line in stdout.lines() {
+                if line.contains("resolution:") {
+                    if let Some(dpi_str) = line.split_whitespace().nth(1) {
+                        if let Some(dpi) = dpi_str.split('x').next() {
+                            if let Ok(dpi) = dpi.parse::<u16>() {
+                                return dpi;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    96
+}
+
+fn default_content() -> String {
+    let mut config = Config::default();
+    config.appearance.dpi = detect_dpi();
+    toml::to_string_pretty(&config).unwrap_or_default()
+}
+
+/// Add the keys of `defaults` that `existing` lacks, leaving everything
+/// already there, comments and layout included, as it is. Keys that another
+/// config layer sets, in `elsewhere`, are left out so its values still apply.
+fn merge_missing(existing: &mut Table, defaults: &Table, elsewhere: Option<&toml::Table>) {
+    for (key, item) in defaults.iter() {
+        let other = elsewhere.and_then(|table| table.get(key));
+        let other_table = other.and_then(toml::Value::as_table);
+        match (existing.get_mut(key), item) {
+            (Some(Item::Table(existing)), Item::Table(defaults)) => {
+                merge_missing(existing, defaults, other_table)
+            }
+            (Some(_), _) => {}
+            (None, Item::Table(defaults)) if other_table.is_some() => {
+                let mut table = Table::new();
+                merge_missing(&mut table, defaults, other_table);
+                if !table.is_empty() {
+                    existing.insert(key, Item::Table(table));
+                }
+            }
+            (None, _) if other.is_some() => {}
+            (None, item) => {
+                existing.insert(key, item.clone());
+            }
+        }
+    }
+}
+
+/// Lines of a unified diff from `old` to `new`, with two lines of context.
+fn diff(old: &str, new: &str) -> Vec<String> {
+    const CONTEXT: usize = 2;
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence lengths of every pair of suffixes
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+    let mut edits = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            edits.push(('+', new[j]));
+            j += 1;
+        } else {
+            edits.push(('-', old[i]));
+            i += 1;
+        }
+    }
+
+    // Show changed lines and those around them, each run under an @@
+    let mut shown = vec![false; edits.len()];
+    for (k, (sign, _)) in edits.iter().enumerate() {
+        if *sign != ' ' {
+            let end = (k + CONTEXT + 1).min(edits.len());
+            shown[k.saturating_sub(CONTEXT)..end].fill(true);
+        }
+    }
+    let mut lines = Vec::new();
+    for (k, (sign, line)) in edits.iter().enumerate() {
+        if !shown[k] {
+            continue;
+        }
+        if k == 0 || !shown[k - 1] {
+            lines.push("@@".to_string());
+        }
+        lines.push(format!("{}{}", sign, line));
+    }
+    lines
+}
+
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    io::stdout().flush().ok();
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input).is_ok() {
+        let input = input.trim().to_lowercase();
+        return input == "y" || input == "yes";
+    }
+    false
+}
+
+/// `--init-config`: create the config file, or add defaults for the keys no
+/// config file sets yet, showing the changes first. Returns whether it
+/// succeeded.
+pub fn init_config(dry_run: bool) -> bool {
+    let path = config::config_path();
+    let defaults = default_content();
+    let existing = match fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            return false;
+        }
+    };
+
+    // A new file starts out empty and gets every key the same way
+    let content = existing.as_deref().unwrap_or_default();
+    let mut document: DocumentMut = match content.parse() {
+        Ok(document) => document,
+        Err(e) => {
+            eprintln!("{}: {}", path.display(), e);
+            eprintln!("Fix the file first, see --check-config.");
+            return false;
+        }
+    };
+    let defaults: DocumentMut = match defaults.parse() {
+        Ok(defaults) => defaults,
+        Err(e) => {
+            eprintln!("default config: {}", e);
+            return false;
+        }
+    };
+    // Keys set by system-wide or included files are theirs to set
+    let (layers, _) = config::read_layers();
+    let elsewhere = config::layers_table(layers.iter().filter(|l| l.path != path));
+    merge_missing(
+        document.as_table_mut(),
+        defaults.as_table(),
+        Some(&elsewhere),
+    );
+    let new_content = document.to_string();
+    if existing.is_some() && new_content == content {
+        println!("{}: up to date", path.display());
+        return true;
+    }
+
+    match &existing {
+        Some(existing) => {
+            println!("Adding missing keys to {}:", path.display());
+            for line in diff(existing, &new_content) {
+                println!("{}", line);
+            }
+        }
+        None if dry_run => {
+            println!("Would create {}:", path.display());
+            print!("{}", new_content);
+        }
+        None => {}
+    }
+    if dry_run {
+        return true;
+    }
+    // Nobody to ask when piped; the changes only ever add keys
+    if existing.is_some() && io::stdin().is_terminal() && !confirm("Write these changes?") {
+        println!("Cancelled. Config file unchanged: {}", path.display());
+        return true;
+    }
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&path, &new_content));
+    match written {
+        Ok(()) => {
+            info!("wrote config to {}", path.display());
+            let verb = if existing.is_some() {
+                "Updated"
+            } else {
+                "Created"
+            };
+            println!("{} config file: {}", verb, path.display());
+            true
+        }
+        Err(e) => {
+            eprintln!("Failed to write {}: {}", path.display(), e);
+            false
+        }
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use x11rb::protocol::xproto::KeyButMask;
+
+pub mod keysym {
+    pub const BACKSPACE: u32 = 0xff08;
+    pub const TAB: u32 = 0xff09;
+    pub const RETURN: u32 = 0xff0d;
+    pub const ESCAPE: u32 = 0xff1b;
+    pub const DELETE: u32 = 0xffff;
+    pub const HOME: u32 = 0xff50;
+    pub const LEFT: u32 = 0xff51;
+    pub const UP: u32 = 0xff52;
+    pub const RIGHT: u32 = 0xff53;
+    pub const DOWN: u32 = 0xff54;
+    pub const PAGE_UP: u32 = 0xff55;
+    pub const PAGE_DOWN: u32 = 0xff56;
+    pub const END: u32 = 0xff57;
+    pub const INSERT: u32 = 0xff63;
+    pub const KP_ENTER: u32 = 0xff8d;
+    pub const F1: u32 = 0xffbe;
+    pub const SPACE: u32 = 0x20;
+
+    /// Shift, Control, Caps Lock, Meta, Alt, Super and Hyper, left and right.
+    pub fn is_modifier(keysym: u32) -> bool {
+        (0xffe1..=0xffee).contains(&keysym)
+    }
+}
+
+const NAMED_KEYS: &[(&str, u32)] = &[
+    ("enter", keysym::RETURN),
+    ("return", keysym::RETURN),
+    ("kp_enter", keysym::KP_ENTER),
+    ("tab", keysym::TAB),
+    ("escape", keysym::ESCAPE),
+    ("esc", keysym::ESCAPE),
+    ("backspace", keysym::BACKSPACE),
+    ("delete", keysym::DELETE),
+    ("del", keysym::DELETE),
+    ("insert", keysym::INSERT),
+    ("home", keysym::HOME),
+    ("end", keysym::END),
+    ("left", keysym::LEFT),
+    ("right", keysym::RIGHT),
+    ("up", keysym::UP),
+    ("down", keysym::DOWN),
+    ("pageup", keysym::PAGE_UP),
+    ("page_up", keysym::PAGE_UP),
+    ("pagedown", keysym::PAGE_DOWN),
+    ("page_down", keysym::PAGE_DOWN),
+    ("space", keysym::SPACE),
+];
+
+/// Letters are compared by their lowercase keysym, and keypad Enter counts
+/// as Enter.
+fn normalize(keysym: u32) -> u32 {
+    match keysym {
+        0x41..=0x5a => keysym + 0x20,
+        keysym::KP_ENTER => keysym::RETURN,
+        _ => keysym,
+    }
+}
+
+/// A key with modifiers, written like `ctrl+shift+enter` or `alt+j`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct KeyChord {
+    pub keysym: u32,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl KeyChord {
+    pub fn from_event(keysym: u32, state: u16) -> Self {
+        Self {
+            keysym: normalize(keysym),
+            ctrl: state & u16::from(KeyButMask::CONTROL) != 0,
+            shift: state & u16::from(KeyButMask::SHIFT) != 0,
+            alt: state & u16::from(KeyButMask::MOD1) != 0,
+        }
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chord = KeyChord {
+            keysym: 0,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        let parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        let (key, modifiers) = match parts.split_last() {
+            // "ctrl++" binds the plus key
+            Some((&"", rest)) if rest.last() == Some(&"") => ("+", &rest[..rest.len() - 1]),
+            Some((key, rest)) => (*key, rest),
+            None => return Err(format!("empty key chord '{}'", s)),
+        };
+        for modifier in modifiers {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => chord.ctrl = true,
+                "shift" => chord.shift = true,
+                "alt" | "mod1" => chord.alt = true,
+                other => return Err(format!("unknown modifier '{}' in '{}'", other, s)),
+            }
+        }
+
+        let lower = key.to_ascii_lowercase();
+        chord.keysym = if let Some(&(_, keysym)) = NAMED_KEYS.iter().find(|(n, _)| *n == lower) {
+            keysym
+        } else if let Some(n) = lower
+            .strip_prefix('f')
+            .and_then(|n| n.parse::<u32>().ok())
+            .filter(|n| (1..=12).contains(n))
+        {
+            keysym::F1 + n - 1
+        } else {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if (' '..='~').contains(&c) => normalize(c as u32),
+                _ => return Err(format!("unknown key '{}' in '{}'", key, s)),
+            }
+        };
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "alt+")?;
+        }
+        if self.shift {
+            write!(f, "shift+")?;
+        }
+        if let Some((name, _)) = NAMED_KEYS.iter().find(|(_, k)| *k == self.keysym) {
+            write!(f, "{}", name)
+        } else if (keysym::F1..keysym::F1 + 12).contains(&self.keysym) {
+            write!(f, "f{}", self.keysym - keysym::F1 + 1)
+        } else {
+            write!(f, "{}", char::from_u32(self.keysym).unwrap_or('?'))
+        }
+    }
+}
+
+/// Something a key can be bound to in `[keys]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Accept,
+    AcceptInTerminal,
+    AcceptHold,
+    Cancel,
+    Complete,
+    Forget,
+    DeleteBack,
+    Clear,
+    MoveLeft,
+    MoveRight,
+    LineStart,
+    LineEnd,
+    SelectNext,
+    SelectPrev,
+    Paste,
+    Undo,
+    Redo,
+}
+
+/// Every action with its name in the config and what it does.
+pub const ACTIONS: &[(Action, &str, &str)] = &[
+    (
+        Action::Accept,
+        "accept",
+        "Launch the selection, or the query if nothing matches",
+    ),
+    (
+        Action::AcceptInTerminal,
+        "accept-in-terminal",
+        "Launch in a terminal; in filter mode, print the query",
+    ),
+    (
+        Action::AcceptHold,
+        "accept-hold",
+        "Launch in a terminal that stays open afterwards",
+    ),
+    (Action::Cancel, "cancel", "Close the window"),
+    (
+        Action::Complete,
+        "complete",
+        "Copy the selection into the query",
+    ),
+    (
+        Action::Forget,
+        "forget",
+        "Remove the selection from the history, after asking",
+    ),
+    (
+        Action::DeleteBack,
+        "delete-back",
+        "Delete the character before the cursor",
+    ),
+    (Action::Clear, "clear", "Clear the query"),
+    (
+        Action::MoveLeft,
+        "move-left",
+        "Move the cursor left, or the selection back",
+    ),
+    (
+        Action::MoveRight,
+        "move-right",
+        "Move the cursor right, or on into the results",
+    ),
+    (
+        Action::LineStart,
+        "line-start",
+        "Move the cursor to the start",
+    ),
+    (Action::LineEnd, "line-end", "Move the cursor to the end"),
+    (
+        Action::SelectNext,
+        "select-next",
+        "Select the next result in the list",
+    ),
+    (
+        Action::SelectPrev,
+        "select-prev",
+        "Select the previous result in the list",
+    ),
+    (Action::Paste, "paste", "Paste the clipboard"),
+    (Action::Undo, "undo", "Undo the last edit"),
+    (Action::Redo, "redo", "Redo the last undone edit"),
+];
+
+/// Bindings before `[keys]` is applied. `accept-hold` comes from
+/// `launcher.hold_key` instead.
+const DEFAULT_BINDINGS: &[(&str, Action)] = &[
+    ("enter", Action::Accept),
+    ("shift+enter", Action::AcceptInTerminal),
+    ("escape", Action::Cancel),
+    ("tab", Action::Complete),
+    ("delete", Action::Forget),
+    ("backspace", Action::DeleteBack),
+    ("ctrl+u", Action::Clear),
+    ("left", Action::MoveLeft),
+    ("right", Action::MoveRight),
+    ("home", Action::LineStart),
+    ("end", Action::LineEnd),
+    ("down", Action::SelectNext),
+    ("up", Action::SelectPrev),
+    ("ctrl+v", Action::Paste),
+    ("ctrl+z", Action::Undo),
+    ("ctrl+shift+z", Action::Redo),
+    ("ctrl+y", Action::Redo),
+];
+
+impl FromStr for Action {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ACTIONS
+            .iter()
+            .find(|(_, name, _)| *name == s)
+            .map(|(action, _, _)| *action)
+            .ok_or_else(|| format!("unknown action '{}', see --list-actions", s))
+    }
+}
+
+/// Key chords and the actions they trigger.
+pub struct Bindings(HashMap<KeyChord, Action>);
+
+impl Bindings {
+    /// The defaults, with `hold_key` bound to `accept-hold` and then each
+    /// `chord = action` of `[keys]` on top. `"none"` unbinds a chord.
+    /// Entries that don't parse are returned as (chord, message) and left
+    /// out.
+    pub fn new(hold_key: &str, keys: &BTreeMap<String, String>) -> (Self, Vec<(String, String)>) {
+        let mut map = HashMap::new();
+        let mut errors = Vec::new();
+        for (chord, action) in DEFAULT_BINDINGS {
+            if let Ok(chord) = chord.parse() {
+                map.insert(chord, *action);
+            }
+        }
+        match hold_key.parse() {
+            Ok(chord) => {
+                map.insert(chord, Action::AcceptHold);
+            }
+            Err(e) => errors.push(("launcher.hold_key".to_string(), e)),
+        }
+        for (chord, action) in keys {
+            let key = format!("keys.{}", chord);
+            let chord: KeyChord = match chord.parse() {
+                Ok(chord) => chord,
+                Err(e) => {
+                    errors.push((key, e));
+                    continue;
+                }
+            };
+            if action == "none" {
+                map.remove(&chord);
+                continue;
+            }
+            match action.parse() {
+                Ok(action) => {
+                    map.insert(chord, action);
+                }
+                Err(e) => errors.push((key, e)),
+            }
+        }
+        (Self(map), errors)
+    }
+
+    /// The action for a key event. Shift is ignored for chords only bound
+    /// without it, so Shift+Left still moves left.
+    pub fn action(&self, keysym: u32, state: u16) -> Option<Action> {
+        let chord = KeyChord::from_event(keysym, state);
+        self.0.get(&chord).copied().or_else(|| {
+            let unshifted = KeyChord {
+                shift: false,
+                ..chord
+            };
+            chord.shift.then(|| self.0.get(&unshifted).copied())?
+        })
+    }
+
+    /// Chords bound to `action`, sorted.
+    pub fn chords(&self, action: Action) -> Vec<String> {
+        let mut chords: Vec<String> = self
+            .0
+            .iter()
+            .filter(|(_, a)| **a == action)
+            .map(|(chord, _)| chord.to_string())
+            .collect();
+        chords.sort();
+        chords
+    }
+}
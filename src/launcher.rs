@@ -1,102 +1,448 @@
-use std::fs::OpenOptions;
-use std::io::Write;
+use std::fs::File;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-use crate::config::{config_dir, Config};
+use crate::app_discovery::find_executable;
+use crate::config::{Config, Platform};
+use crate::rules;
+use crate::shell;
+use crate::spawn::{spawn_detached, spawn_supervised};
+use crate::supervisor;
+use crate::systemd;
+use crate::terminal;
+use crate::wslpath;
+
+/// Prefix that opts a query into full shell syntax (pipes, redirects, ...).
+pub const SHELL_PREFIX: char = '!';
 
 pub struct LaunchResult {
     pub success: bool,
     pub command: String,
-    pub needs_delay: bool,
+    pub error: Option<String>,
+    /// Whether the command was (to be) started inside a terminal
+    pub in_terminal: bool,
 }
 
-fn log(msg: &str) {
-    let path = config_dir().join("ctrl-space-wsl.log");
-    if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(path) {
-        let _ = writeln!(f, "{}", msg);
+impl LaunchResult {
+    fn failed(error: impl ToString) -> Self {
+        Self {
+            success: false,
+            command: String::new(),
+            error: Some(error.to_string()),
+            in_terminal: false,
+        }
     }
 }
 
-pub fn launch_command(input: &str, use_terminal: bool, config: &Config) -> LaunchResult {
-    let parts: Vec<&str> = input.split_whitespace().collect();
-    if parts.is_empty() {
-        return LaunchResult {
-            success: false,
-            command: String::new(),
-            needs_delay: false,
-        };
+/// The OS error text without the " (os error N)" suffix.
+fn describe(e: &std::io::Error) -> String {
+    let text = e.to_string();
+    match text.find(" (os error") {
+        Some(end) => text[..end].to_string(),
+        None => text,
     }
+}
 
-    let program = parts[0];
-    let normalized_cmd = parts.join(" ");
-    let home = std::env::var("HOME").unwrap_or_else(|_| "/home/dev".to_string());
+/// Parse the query into an argv and the canonical form recorded in history.
+/// A query starting with `SHELL_PREFIX` is handed to bash as a script.
+fn parse_input(input: &str, home: &str) -> Result<(Vec<String>, String), shell::SplitError> {
+    if let Some(script) = input.trim_start().strip_prefix(SHELL_PREFIX) {
+        let script = script.trim();
+        let argv = vec!["bash".to_string(), "-c".to_string(), script.to_string()];
+        return Ok((argv, format!("{}{}", SHELL_PREFIX, script)));
+    }
+    let words = shell::split_words(input)?;
+    let canonical = words
+        .iter()
+        .map(shell::quote_word)
+        .collect::<Vec<_>>()
+        .join(" ");
+    let argv = words.iter().map(|w| w.expand(home)).collect();
+    Ok((argv, canonical))
+}
 
-    let resolved_program = std::fs::canonicalize(program)
-        .map(|p| p.to_string_lossy().to_string())
-        .unwrap_or_else(|_| program.to_string());
+fn translate_paths_for(program: &str, config: &Config) -> bool {
+    if !config.launcher.translate_paths {
+        return false;
+    }
+    let name = std::path::Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    !config.launcher.translate_paths_exclude.iter().any(|e| {
+        let e = e.to_lowercase();
+        name == e || name == format!("{}.exe", e)
+    })
+}
 
-    let is_windows_exe = resolved_program.to_lowercase().ends_with(".exe");
+/// Something typed into the query that should be opened rather than run.
+enum OpenTarget {
+    Url(String),
+    Path(PathBuf),
+}
 
-    log(&format!(
-        "launching: {} -> {} (windows_exe={}, use_terminal={})",
-        program, resolved_program, is_windows_exe, use_terminal
-    ));
+fn is_url(word: &str) -> bool {
+    if word.starts_with("mailto:") {
+        return true;
+    }
+    match word.split_once("://") {
+        Some((scheme, rest)) => {
+            !rest.is_empty()
+                && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c))
+        }
+        None => false,
+    }
+}
 
-    let result = if use_terminal {
-        let terminal_parts: Vec<&str> = config.launcher.terminal.split_whitespace().collect();
-        if terminal_parts.is_empty() {
-            return LaunchResult {
-                success: false,
-                command: String::new(),
-                needs_delay: false,
-            };
+/// Detect a URL, or a path to an existing directory or non-executable file.
+/// Executables keep being launched, so `./build.sh` still runs the script.
+/// `word` has had its `~` expanded already.
+fn open_target(word: &str, home: &str) -> Option<OpenTarget> {
+    if is_url(word) {
+        return Some(OpenTarget::Url(word.to_string()));
+    }
+    let looks_like_path =
+        word.starts_with('/') || word.starts_with("./") || word.starts_with("../");
+    if !looks_like_path {
+        return None;
+    }
+    let path = Path::new(home).join(word);
+    if path.is_dir() || (path.is_file() && find_executable(&path.to_string_lossy()).is_none()) {
+        Some(OpenTarget::Path(path))
+    } else {
+        None
+    }
+}
+
+/// Build the opener command line: the configured one, or `wslview` /
+/// `explorer.exe` on WSL and `xdg-open` elsewhere.
+fn opener_command(target: &OpenTarget, config: &Config) -> Result<Command, String> {
+    let mut argv = if config.launcher.opener.trim().is_empty() {
+        if !wslpath::is_wsl() {
+            vec!["xdg-open".to_string()]
+        } else if find_executable("wslview").is_some() {
+            vec!["wslview".to_string()]
+        } else {
+            vec!["explorer.exe".to_string()]
         }
-        let terminal_program = terminal_parts[0];
-        let is_windows_terminal = terminal_program.to_lowercase().ends_with(".exe");
-        let mut cmd = Command::new(terminal_program);
-        cmd.args(&terminal_parts[1..]);
-        cmd.args(&parts);
-        cmd.current_dir(&home);
-        if is_windows_terminal {
-            cmd.stdin(std::process::Stdio::null());
-            cmd.stdout(std::process::Stdio::null());
-            cmd.stderr(std::process::Stdio::null());
-        }
-        cmd.spawn()
-    } else if is_windows_exe {
-        let mut cmd = Command::new(&resolved_program);
-        cmd.args(&parts[1..]);
-        cmd.current_dir(&home);
-        cmd.stdin(std::process::Stdio::null());
-        cmd.stdout(std::process::Stdio::null());
-        cmd.stderr(std::process::Stdio::null());
-        cmd.spawn()
     } else {
-        let shell_cmd = format!("nohup {} >/dev/null 2>&1 &", normalized_cmd);
-        let mut cmd = Command::new("bash");
-        cmd.args(["-c", &shell_cmd]);
-        cmd.current_dir(&home);
-        cmd.spawn()
+        match shell::split(&config.launcher.opener) {
+            Ok(argv) if !argv.is_empty() => argv,
+            _ => return Err("invalid opener command".to_string()),
+        }
+    };
+
+    let arg = match target {
+        OpenTarget::Url(url) => url.clone(),
+        OpenTarget::Path(path) if argv[0].to_lowercase().ends_with(".exe") => {
+            wslpath::to_windows(path).unwrap_or_else(|| path.to_string_lossy().into_owned())
+        }
+        OpenTarget::Path(path) => path.to_string_lossy().into_owned(),
+    };
+    argv.push(arg);
+
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    Ok(cmd)
+}
+
+/// Keeps the terminal open after the command exits, showing its status.
+const HOLD_SCRIPT: &str = r#""$@"; status=$?; printf '\n[%s exited with status %d] Press any key to close...' "$1" "$status"; read -rsn1"#;
+
+fn terminal_command(
+    config: &Config,
+    argv: &[String],
+    cwd: &Path,
+    hold: bool,
+) -> Result<Command, String> {
+    let template = terminal::resolve(&config.launcher.terminal);
+    let title = argv
+        .first()
+        .map(|p| {
+            Path::new(p)
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_else(|| p.clone())
+        })
+        .unwrap_or_default();
+    let held;
+    let argv = if hold {
+        held = ["bash", "-c", HOLD_SCRIPT, "bash"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(argv.iter().cloned())
+            .collect::<Vec<_>>();
+        &held
+    } else {
+        argv
     };
+    terminal::command(&template, argv, cwd, &title).inspect_err(|e| error!("terminal: {}", e))
+}
+
+/// Spawn with the supervisor watching the child, falling back to a plain
+/// detached spawn if the per-launch log can't be created.
+fn spawn_watched(cmd: &Command, command: &str, config: &Config) -> std::io::Result<u32> {
+    if !config.launcher.supervise {
+        return spawn_detached(cmd);
+    }
+    let (stderr, log_path) = match supervisor::create_log(&cmd.get_program().to_string_lossy()) {
+        Ok(log) => log,
+        Err(e) => {
+            warn!("supervise: cannot create log: {}", e);
+            return spawn_detached(cmd);
+        }
+    };
+    let pid = spawn_supervised(cmd, &stderr)?;
+    supervisor::watch(pid, command.to_string(), log_path, config);
+    Ok(pid)
+}
 
-    let needs_delay = use_terminal || is_windows_exe;
+/// Spawn `scoped`, the systemd-run wrapper of `cmd`, and wait for it to start
+/// the program. The supervisor log is named after the program, not after
+/// systemd-run.
+fn spawn_in_scope(
+    scoped: &Command,
+    cmd: &Command,
+    command: &str,
+    config: &Config,
+) -> std::io::Result<u32> {
+    let program = cmd.get_program().to_string_lossy();
+    let log = if config.launcher.supervise {
+        supervisor::create_log(&program)
+            .inspect_err(|e| warn!("supervise: cannot create log: {}", e))
+            .ok()
+    } else {
+        None
+    };
+    // Kept as our child either way, to tell whether systemd-run got as far
+    // as starting the program
+    let pid = match log {
+        Some((ref stderr, _)) => spawn_supervised(scoped, stderr)?,
+        None => spawn_supervised(scoped, &File::options().write(true).open("/dev/null")?)?,
+    };
+    if !systemd::started(pid) {
+        return Err(std::io::Error::other(
+            "systemd-run exited without starting it",
+        ));
+    }
+    match log {
+        Some((_, log_path)) => supervisor::watch(pid, command.to_string(), log_path, config),
+        None => supervisor::reap_later(pid),
+    }
+    Ok(pid)
+}
 
-    match result {
-        Ok(_) => {
-            log("spawn: ok");
+/// Run inside a transient systemd scope when enabled and a user manager is
+/// around, otherwise spawn the command as is. If the scope can't be set up,
+/// because of invalid resource controls or systemd-run failing, the command
+/// is spawned as is too.
+fn spawn_scoped(cmd: &Command, command: &str, config: &Config) -> std::io::Result<u32> {
+    let scope = &config.launcher.systemd;
+    if !scope.enabled {
+        return spawn_watched(cmd, command, config);
+    }
+    if !systemd::available() {
+        debug!("systemd: no user manager, launching without a scope");
+        return spawn_watched(cmd, command, config);
+    }
+    let scoped = match systemd::scope_command(cmd, scope) {
+        Ok(scoped) => scoped,
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => {
+            warn!("systemd: {}, launching without a scope", e);
+            return spawn_watched(cmd, command, config);
+        }
+        Err(e) => return Err(e),
+    };
+    debug!("systemd: {:?}", scoped.get_args().collect::<Vec<_>>());
+    // The program was found above, so a failure here is systemd-run's own
+    spawn_in_scope(&scoped, cmd, command, config).or_else(|e| {
+        warn!(
+            "systemd: systemd-run failed ({}), launching without a scope",
+            e
+        );
+        spawn_watched(cmd, command, config)
+    })
+}
+
+fn spawn(cmd: &Command, command: String, in_terminal: bool, config: &Config) -> LaunchResult {
+    match spawn_scoped(cmd, &command, config) {
+        Ok(pid) => {
+            info!("spawn: ok pid {}", pid);
             LaunchResult {
                 success: true,
-                command: normalized_cmd,
-                needs_delay,
+                command,
+                error: None,
+                in_terminal,
             }
         }
         Err(e) => {
-            log(&format!("spawn: error {}", e));
+            error!("spawn: error {}", e);
+            let error = if in_terminal {
+                format!(
+                    "terminal '{}': {}",
+                    cmd.get_program().to_string_lossy(),
+                    describe(&e)
+                )
+            } else {
+                describe(&e)
+            };
             LaunchResult {
-                success: false,
-                command: String::new(),
-                needs_delay: false,
+                error: Some(error),
+                in_terminal,
+                ..LaunchResult::failed("")
             }
         }
     }
 }
+
+/// Open a URL or path with the opener. With `use_terminal`, a terminal is
+/// started in the directory instead (the parent directory for files).
+fn open(target: OpenTarget, command: String, use_terminal: bool, config: &Config) -> LaunchResult {
+    let is_dir_target = matches!(target, OpenTarget::Path(_));
+    let cmd = match target {
+        OpenTarget::Path(ref path) if use_terminal => {
+            let dir = if path.is_dir() {
+                path.as_path()
+            } else {
+                path.parent().unwrap_or(Path::new("/"))
+            };
+            let shell = std::env::var("SHELL").unwrap_or_else(|_| "bash".to_string());
+            info!("open: terminal in {}", dir.display());
+            terminal_command(config, &[shell], dir, false)
+        }
+        _ => opener_command(&target, config),
+    };
+    let mut cmd = match cmd {
+        Ok(cmd) => cmd,
+        Err(e) => return LaunchResult::failed(e),
+    };
+    if cmd.get_current_dir().is_none() {
+        let home = std::env::var("HOME").unwrap_or_else(|_| "/home/dev".to_string());
+        cmd.current_dir(home);
+    }
+    info!("open: {} with {:?}", command, cmd.get_program());
+    spawn(&cmd, command, use_terminal && is_dir_target, config)
+}
+
+/// How a command relates to the terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerminalMode {
+    /// Launch directly, unless a rule asks for a terminal
+    Off,
+    /// Run inside the configured terminal
+    On,
+    /// Run inside the terminal and keep it open after the command exits
+    Hold,
+}
+
+pub fn launch_command(input: &str, mode: TerminalMode, config: &Config) -> LaunchResult {
+    let use_terminal = mode != TerminalMode::Off;
+    let home = std::env::var("HOME").unwrap_or_else(|_| "/home/dev".to_string());
+    let (parts, normalized_cmd) = match parse_input(input, &home) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            warn!("parse: error {} in {:?}", e, input);
+            return LaunchResult::failed(e);
+        }
+    };
+    if parts.is_empty() {
+        return LaunchResult::failed("empty command");
+    }
+
+    let is_script = input.trim_start().starts_with(SHELL_PREFIX);
+    if parts.len() == 1 && !is_script {
+        if let Some(target) = open_target(&parts[0], &home) {
+            return open(target, normalized_cmd, use_terminal, config);
+        }
+    }
+
+    let rule = if is_script {
+        None
+    } else {
+        rules::find_rule(&config.launcher.rules, &parts[0])
+    };
+
+    let mut argv = parts.clone();
+    let mut use_terminal = use_terminal;
+    let mut hold = mode == TerminalMode::Hold;
+    let mut cwd = PathBuf::from(&home);
+    if let Some(rule) = rule {
+        argv.splice(1..1, rule.args_prefix.iter().cloned());
+        argv.extend(rule.args_suffix.iter().cloned());
+        // An explicit `terminal = false` wins over Shift+Enter, for GUI
+        // programs that have no use for one
+        if let Some(terminal) = rule.terminal {
+            use_terminal = terminal;
+            hold &= terminal;
+        }
+        // Holding only makes sense in a terminal, so it implies one
+        hold |= rule.hold.unwrap_or(false);
+        use_terminal |= hold;
+        if let Some(ref dir) = rule.cwd {
+            cwd = PathBuf::from(shell::expand_tilde(dir, &home));
+        }
+    }
+
+    let program = argv[0].as_str();
+
+    let resolved_program = std::fs::canonicalize(program)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| program.to_string());
+
+    let is_windows_exe = match rule.and_then(|r| r.platform) {
+        Some(platform) => platform == Platform::Windows,
+        None => resolved_program.to_lowercase().ends_with(".exe"),
+    };
+    let translate_paths = is_windows_exe
+        && rule
+            .and_then(|r| r.translate_paths)
+            .unwrap_or_else(|| translate_paths_for(program, config));
+
+    info!(
+        "launching: {} -> {} (windows_exe={}, use_terminal={}, hold={}, rule={})",
+        normalized_cmd,
+        resolved_program,
+        is_windows_exe,
+        use_terminal,
+        hold,
+        rule.map(|r| r.name.as_str()).unwrap_or("none")
+    );
+
+    let mut cmd = if use_terminal {
+        match terminal_command(config, &argv, &cwd, hold) {
+            Ok(cmd) => cmd,
+            Err(e) => return LaunchResult::failed(e),
+        }
+    } else {
+        let mut cmd = if resolved_program.to_lowercase().ends_with(".exe") {
+            Command::new(&resolved_program)
+        } else {
+            Command::new(program)
+        };
+        if translate_paths {
+            for arg in &argv[1..] {
+                match wslpath::translate_arg(arg) {
+                    Some(translated) => {
+                        debug!("translate: {} -> {}", arg, translated);
+                        cmd.arg(translated);
+                    }
+                    None => {
+                        cmd.arg(arg);
+                    }
+                }
+            }
+        } else {
+            cmd.args(&argv[1..]);
+        }
+        cmd
+    };
+    cmd.current_dir(&cwd);
+    if let Some(rule) = rule {
+        cmd.envs(&rule.env);
+    }
+
+    spawn(&cmd, normalized_cmd, use_terminal, config)
+}
@@ -1,29 +1,246 @@
 use std::fs;
-use std::process::{self, Command};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, Instant};
 
-use crate::config::config_dir;
+use crate::config::runtime_dir;
 
-pub fn kill_others() {
+/// How long a running instance gets to answer before it is considered stale.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Requests a second invocation sends to the running instance.
+pub const REQUEST_CLOSE: &str = "close";
+pub const REQUEST_FOCUS: &str = "focus";
+/// Only checks that an instance is alive and answering.
+pub const REQUEST_PING: &str = "ping";
+/// What instances from before replies were JSON answer with.
+const LEGACY_REPLY_OK: &str = "ok";
+
+/// Name of the abstract socket, which has no file to clean up. It is scoped
+/// to the user so instances of different users don't see each other.
+pub fn socket_name() -> String {
+    format!("ctrl-space-wsl-{}", unsafe { libc::getuid() })
+}
+
+fn socket_addr() -> io::Result<SocketAddr> {
+    SocketAddr::from_abstract_name(socket_name().as_bytes())
+}
+
+/// File holding the pid of the instance listening on the socket.
+pub fn pid_path() -> PathBuf {
+    runtime_dir().join("pid")
+}
+
+/// Connect to the running instance's socket.
+pub fn connect() -> io::Result<UnixStream> {
+    UnixStream::connect_addr(&socket_addr()?)
+}
+
+/// Whether a reply, `{"ok":true}` or the older plain `ok`, reports success.
+fn is_ok(reply: &str) -> bool {
+    reply.trim() == LEGACY_REPLY_OK
+        || serde_json::from_str::<serde_json::Value>(reply).is_ok_and(|v| v["ok"] == true)
+}
+
+/// Send `request` to a running instance. Returns true if it answered, in
+/// which case this process has nothing left to do.
+pub fn notify_running(request: &str) -> bool {
+    let stream = match connect() {
+        Ok(stream) => stream,
+        Err(_) => return false,
+    };
+    let _ = stream.set_read_timeout(Some(REPLY_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(REPLY_TIMEOUT));
+    if writeln!(&stream, "{}", request).is_err() {
+        warn!("running instance does not accept requests");
+        return false;
+    }
+    let mut reply = String::new();
+    match BufReader::new(&stream).read_line(&mut reply) {
+        Ok(_) if is_ok(&reply) => {
+            info!("running instance handled '{}'", request);
+            true
+        }
+        _ => {
+            warn!("running instance did not answer '{}', it is stale", request);
+            false
+        }
+    }
+}
+
+/// Ask the process recorded in the pid file to terminate, if it is another
+/// instance of this program. Used when it holds the socket but is stuck.
+fn terminate_stale() {
     let my_pid = process::id();
     let my_exe = fs::read_link("/proc/self/exe").ok();
+    let pid_path = pid_path();
+    let Some(old_pid) = fs::read_to_string(&pid_path)
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+    else {
+        return;
+    };
+    if old_pid == my_pid || fs::read_link(format!("/proc/{}/exe", old_pid)).ok() != my_exe {
+        return;
+    }
+    info!("terminating stale instance {}", old_pid);
+    unsafe { libc::kill(old_pid as i32, libc::SIGTERM) };
+    // Give it a moment to exit and release the socket
+    for _ in 0..20 {
+        if !std::path::Path::new(&format!("/proc/{}", old_pid)).exists() {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
 
-    std::thread::spawn(move || {
-        let pid_path = config_dir().join("pid");
-
-        if let Ok(old_pid_str) = fs::read_to_string(&pid_path) {
-            if let Ok(old_pid) = old_pid_str.trim().parse::<u32>() {
-                if old_pid != my_pid {
-                    let old_exe = fs::read_link(format!("/proc/{}/exe", old_pid)).ok();
-                    if old_exe == my_exe {
-                        let _ = Command::new("kill")
-                            .args(["-TERM", &old_pid.to_string()])
-                            .output();
+fn bind() -> io::Result<UnixListener> {
+    let listener = UnixListener::bind_addr(&socket_addr()?)?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Become the single instance: listen on the socket and record our pid.
+/// If a stale instance still holds the socket, it is terminated first. On
+/// failure we run without a socket rather than not at all; that includes
+/// another instance having claimed the socket since we last asked, which
+/// the caller can hand over to.
+pub fn listen() -> Option<UnixListener> {
+    let listener = match bind() {
+        Ok(listener) => Some(listener),
+        Err(e) if e.kind() == io::ErrorKind::AddrInUse => {
+            if notify_running(REQUEST_PING) {
+                info!("another instance started meanwhile");
+                return None;
+            }
+            terminate_stale();
+            bind()
+                .inspect_err(|e| warn!("cannot listen on instance socket: {}", e))
+                .ok()
+        }
+        Err(e) => {
+            warn!("cannot listen on instance socket: {}", e);
+            None
+        }
+    };
+
+    let pid_path = pid_path();
+    let _ = fs::create_dir_all(runtime_dir());
+    if let Err(e) = fs::write(&pid_path, process::id().to_string()) {
+        warn!("cannot write {}: {}", pid_path.display(), e);
+    }
+    listener
+}
+
+/// A client connection whose request line hasn't fully arrived yet.
+pub struct Connection {
+    stream: UnixStream,
+    buf: Vec<u8>,
+    opened: Instant,
+}
+
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+/// The uid of the process at the other end of `stream`.
+fn peer_uid(stream: &UnixStream) -> io::Result<u32> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(cred.uid)
+}
+
+/// Accept every pending connection from our own user. An abstract socket
+/// has no file permissions, so other users could connect too; they are
+/// dropped here.
+pub fn accept(listener: &UnixListener, connections: &mut Vec<Connection>) {
+    let my_uid = unsafe { libc::getuid() };
+    while let Ok((stream, _)) = listener.accept() {
+        match peer_uid(&stream) {
+            Ok(uid) if uid == my_uid => {}
+            Ok(uid) => {
+                warn!("refused instance connection from uid {}", uid);
+                continue;
+            }
+            Err(e) => {
+                warn!("cannot check instance connection: {}", e);
+                continue;
+            }
+        }
+        if stream.set_nonblocking(true).is_ok() {
+            connections.push(Connection {
+                stream,
+                buf: Vec::new(),
+                opened: Instant::now(),
+            });
+        }
+    }
+}
+
+/// Read what has arrived on `connections` without blocking and take those
+/// with a complete request line. Connections that close early or stay
+/// silent too long are dropped.
+pub fn take_requests(connections: &mut Vec<Connection>) -> Vec<(UnixStream, String)> {
+    let mut requests = Vec::new();
+    let mut i = 0;
+    while i < connections.len() {
+        let connection = &mut connections[i];
+        let mut chunk = [0u8; 4096];
+        let complete = loop {
+            match (&connection.stream).read(&mut chunk) {
+                // Closed before sending a whole line
+                Ok(0) => break Some(false),
+                Ok(n) => {
+                    connection.buf.extend_from_slice(&chunk[..n]);
+                    if connection.buf.contains(&b'\n') {
+                        break Some(true);
                     }
                 }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break None,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => break Some(false),
+            }
+        };
+        match complete {
+            Some(true) => {
+                let connection = connections.swap_remove(i);
+                let line = connection.buf.split(|&b| b == b'\n').next().unwrap_or(&[]);
+                let request = String::from_utf8_lossy(line).trim().to_string();
+                requests.push((connection.stream, request));
+            }
+            None if connection.opened.elapsed() < REPLY_TIMEOUT => i += 1,
+            _ => {
+                connections.swap_remove(i);
             }
         }
+    }
+    requests
+}
 
-        let _ = fs::create_dir_all(config_dir());
-        let _ = fs::write(&pid_path, my_pid.to_string());
-    });
+pub fn reply(mut stream: UnixStream, reply: &str) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_write_timeout(Some(REPLY_TIMEOUT));
+    let _ = writeln!(stream, "{}", reply);
 }
@@ -0,0 +1,172 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::state_dir;
+
+/// Rotate once the log grows past this many bytes.
+const MAX_SIZE: u64 = 1024 * 1024;
+/// Rotated files kept next to the log (`.1` is the most recent).
+const MAX_ROTATED: u32 = 3;
+
+pub const ENV_VAR: &str = "CTRL_SPACE_WSL_LOG";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(format!(
+                "invalid log level '{}' (expected error, warn, info, debug or trace)",
+                s
+            )),
+        }
+    }
+}
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Set the maximum level: `level` (from `--log-level`) wins over the
+/// `CTRL_SPACE_WSL_LOG` environment variable, which wins over `info`.
+pub fn init(level: Option<Level>) {
+    let level = level
+        .or_else(|| std::env::var(ENV_VAR).ok()?.parse().ok())
+        .unwrap_or(Level::Info);
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+pub fn log_path() -> PathBuf {
+    state_dir().join("ctrl-space-wsl.log")
+}
+
+/// Format a Unix timestamp as UTC `YYYY-MM-DDTHH:MM:SS.mmmZ`.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Civil date from days since the epoch (Howard Hinnant's algorithm)
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
+
+fn rotate(path: &PathBuf) {
+    let rotated = |n: u32| PathBuf::from(format!("{}.{}", path.display(), n));
+    for n in (1..MAX_ROTATED).rev() {
+        let _ = fs::rename(rotated(n), rotated(n + 1));
+    }
+    let _ = fs::rename(path, rotated(1));
+}
+
+/// Append a line to the log. Use the `error!`..`trace!` macros instead.
+pub fn write(level: Level, module: &str, args: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    let module = module.split_once("::").map_or("main", |(_, m)| m);
+    let line = format!(
+        "{} {:5} [{}] {}\n",
+        timestamp(),
+        level.as_str(),
+        module,
+        args
+    );
+
+    let _guard = WRITE_LOCK.lock();
+    let path = log_path();
+    if let Some(parent) = path.parent() {
+        let _ = fs::create_dir_all(parent);
+    }
+    if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_SIZE) {
+        rotate(&path);
+    }
+    if let Ok(mut f) = OpenOptions::new().create(true).append(true).open(&path) {
+        let _ = f.write_all(line.as_bytes());
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Error, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Warn, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Info, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Debug, module_path!(), format_args!($($arg)*))
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        $crate::log::write($crate::log::Level::Trace, module_path!(), format_args!($($arg)*))
+    };
+}
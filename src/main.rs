@@ -1,19 +1,39 @@
+#[macro_use]
+mod log;
+
 mod app_discovery;
+mod check;
+mod color;
 mod config;
+mod dmenu;
+mod environment;
 mod filter;
 mod frequency;
+mod init;
+mod keys;
 mod launcher;
 mod lock;
+mod remote;
+mod rules;
+mod shell;
+mod spawn;
+mod supervisor;
+mod systemd;
+mod terminal;
+mod theme;
+mod watch;
+mod wslpath;
 
 mod ui;
 
 use std::env;
 use std::io::{self, BufRead, IsTerminal};
 use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
 
-use config::Config;
+use config::{Config, Launcher};
 use frequency::Frequency;
-use lock::kill_others;
+use keys::Bindings;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -33,55 +53,136 @@ fn stdin_has_data() -> bool {
 }
 
 fn print_info() {
-    let dir = config::config_dir();
-
     println!("ctrl-space-wsl \n");
     println!("Version:          v{}", VERSION);
-    println!("Config:           {}", dir.join("config.toml").display());
-    println!("Cache:            {}", dir.join("freq.txt").display());
+    println!("Config:           {}", config::config_path().display());
+    let sources = Config::load().sources;
+    if sources.is_empty() {
+        println!("Loaded from:      (defaults)");
+    }
+    for (i, source) in sources.iter().enumerate() {
+        let label = if i == 0 { "Loaded from:" } else { "" };
+        println!("{:<18}{}", label, source.display());
+    }
+    println!("Themes:           {}", theme::themes_dir().display());
+    println!("History:          {}", frequency::data_path().display());
+    println!("Font cache:       {}", ui::font_cache_path().display());
+    println!("Login env cache:  {}", environment::cache_path().display());
+    println!("Pid:              {}", lock::pid_path().display());
+    println!("Socket:           @{}", lock::socket_name());
+    println!("Log:              {}", log::log_path().display());
+}
+
+/// Print every action with its default keys, and the configured ones where
+/// `[keys]` changes them.
+fn list_actions() {
+    let defaults = Bindings::new(&Launcher::default().hold_key, &Default::default()).0;
+    let config = Config::load();
+    let current = Bindings::new(&config.launcher.hold_key, &config.keys).0;
+    for &(action, name, description) in keys::ACTIONS {
+        let default = defaults.chords(action);
+        println!("{:<20} {:<26} {}", name, default.join(", "), description);
+        let configured = current.chords(action);
+        if configured != default {
+            let configured = if configured.is_empty() {
+                "(none)".to_string()
+            } else {
+                configured.join(", ")
+            };
+            println!("{:<20} configured: {}", "", configured);
+        }
+    }
+}
+
+/// Values of every `FLAG VALUE` or `FLAG=VALUE` in `args`.
+fn arg_values(args: &[String], flag: &str) -> Vec<String> {
+    let prefix = format!("{}=", flag);
+    args.iter()
+        .enumerate()
+        .filter_map(|(i, arg)| {
+            if arg == flag {
+                Some(args.get(i + 1).cloned().unwrap_or_default())
+            } else {
+                arg.strip_prefix(&prefix).map(str::to_string)
+            }
+        })
+        .collect()
+}
+
+/// Value of `--log-level LEVEL` or `--log-level=LEVEL`, if given.
+fn log_level_arg(args: &[String]) -> Option<Result<log::Level, String>> {
+    let value = arg_values(args, "--log-level").pop()?;
+    Some(value.parse())
+}
+
+/// Apply `--config PATH` and `-o section.key=value` to every config load.
+fn config_source_args(args: &[String]) -> Result<(), String> {
+    let path = arg_values(args, "--config").pop().map(PathBuf::from);
+    let overrides = arg_values(args, "-o")
+        .iter()
+        .map(|o| o.parse())
+        .collect::<Result<Vec<config::Override>, _>>()?;
+    config::set_source(path, overrides)
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
+    // A `msg` payload is sent as it is, none of it is an option of ours
+    let options = match args.get(1) {
+        Some(subcommand) if subcommand == "msg" => &args[..1],
+        _ => &args[..],
+    };
+    match log_level_arg(options) {
+        Some(Ok(level)) => log::init(Some(level)),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+        None => log::init(None),
+    }
+    config::migrate_files();
+    // dmenu's flags clash with ours (-i), so they are handled on their own
+    if dmenu::is_invoked(options) {
+        std::process::exit(dmenu::run(&args));
+    }
+    if let Err(e) = config_source_args(options) {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+    if args.get(1).is_some_and(|a| a == "msg") {
+        std::process::exit(remote::run_client(&args[2..]));
+    }
     if args.iter().any(|a| a == "--info" || a == "-i") {
         print_info();
         std::process::exit(0);
     }
-    if args.iter().any(|a| a == "--init-config") {
-        match config::create_default_config(false) {
-            Ok(config::CreateConfigResult::Created(path)) => {
-                println!("Created config file: {}", path.display());
-                std::process::exit(0);
-            }
-            Ok(config::CreateConfigResult::NeedsConfirmation(path)) => {
-                if config::confirm_overwrite() {
-                    match config::create_default_config(true) {
-                        Ok(config::CreateConfigResult::Created(p)) => {
-                            println!("Created config file: {}", p.display());
-                            std::process::exit(0);
-                        }
-                        _ => {
-                            eprintln!("Failed to create config");
-                            std::process::exit(1);
-                        }
-                    }
-                } else {
-                    println!("Cancelled. Config file unchanged: {}", path.display());
-                    std::process::exit(0);
-                }
-            }
-
-            Err(e) => {
-                eprintln!("Failed to create config: {}", e);
-                std::process::exit(1);
-            }
+    if args.iter().any(|a| a == "--list-themes") {
+        let current = Config::load().appearance.theme;
+        for (name, source) in theme::list() {
+            let marker = if name == current { '*' } else { ' ' };
+            println!("{} {:<18} {}", marker, name, source);
         }
+        std::process::exit(0);
+    }
+    if args.iter().any(|a| a == "--list-actions") {
+        list_actions();
+        std::process::exit(0);
+    }
+    if args.iter().any(|a| a == "--check-config") {
+        let ok = check::check_config();
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+    if args.iter().any(|a| a == "--init-config") {
+        let dry_run = args.iter().any(|a| a == "--dry-run");
+        let ok = init::init_config(dry_run);
+        std::process::exit(if ok { 0 } else { 1 });
     }
 
     // Read from stdin if it's not a terminal (piped) AND stdin has data available.
     // When launched via wslg.exe, stdin is not a terminal but also has no data,
     // so we'd block forever waiting for EOF. Check if stdin is ready first.
-    let stdin_items: Vec<String> = if !io::stdin().is_terminal() && stdin_has_data() {
+    let daemon = args.iter().any(|a| a == "--daemon");
+    let stdin_items: Vec<String> = if !daemon && !io::stdin().is_terminal() && stdin_has_data() {
         io::stdin()
             .lock()
             .lines()
@@ -94,13 +195,43 @@ fn main() {
 
     // Filter mode only if we actually received piped content
     let filter_mode = !stdin_items.is_empty();
+    debug!(
+        "starting v{} (filter_mode={}, daemon={}, {} stdin items)",
+        VERSION,
+        filter_mode,
+        daemon,
+        stdin_items.len()
+    );
 
     let config = Config::load();
 
-    let (frequency, apps) = if filter_mode {
-        (Frequency::default(), stdin_items)
+    let (frequency, apps, listener) = if filter_mode {
+        (Frequency::default(), stdin_items, None)
     } else {
-        kill_others();
+        let request = if config.launcher.second_instance == "focus" {
+            lock::REQUEST_FOCUS
+        } else {
+            lock::REQUEST_CLOSE
+        };
+        let hand_over = || {
+            if daemon {
+                if lock::notify_running(lock::REQUEST_PING) {
+                    eprintln!("ctrl-space-wsl is already running");
+                    std::process::exit(1);
+                }
+            } else if lock::notify_running(request) {
+                std::process::exit(0);
+            }
+        };
+        hand_over();
+        // Claim the socket first: capturing the login environment can take
+        // seconds, and another instance starting meanwhile must find this one
+        let listener = lock::listen();
+        if listener.is_none() {
+            // One may have started since we asked
+            hand_over();
+        }
+        environment::apply(&config);
         let freq = Frequency::load();
         let apps = if freq.is_empty() {
             app_discovery::discover_apps()
@@ -108,11 +239,17 @@ fn main() {
             freq.refresh_in_background();
             freq.apps()
         };
-        (freq, apps)
+        (freq, apps, listener)
     };
 
-    if let Err(e) = ui::run(config, frequency, apps, filter_mode) {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    match ui::run(config, frequency, apps, filter_mode, listener, daemon) {
+        Ok(Some(selection)) if filter_mode => println!("{}", selection),
+        Ok(_) => {}
+        Err(e) => {
+            error!("{}", e);
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
     }
+    supervisor::wait_all();
 }
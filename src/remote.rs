@@ -0,0 +1,147 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::lock;
+
+/// Messages accepted on the instance socket, one JSON object per line with
+/// the command name in `"command"`, e.g. `{"command":"set-query","query":"vi"}`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    Show,
+    Hide,
+    SetQuery { query: String },
+    SetItems { items: Vec<String> },
+    GetState,
+    AwaitSelection,
+}
+
+#[derive(Debug, Serialize)]
+pub struct State {
+    pub visible: bool,
+    pub query: String,
+    /// The highlighted entry, if anything matches
+    pub selected: Option<String>,
+    pub items: usize,
+    /// Whether the items were replaced with `set-items`
+    pub custom_items: bool,
+}
+
+/// JSON messages start with `{`; anything else is a plain request word.
+pub fn is_message(line: &str) -> bool {
+    line.starts_with('{')
+}
+
+pub fn parse(line: &str) -> Result<Request, String> {
+    serde_json::from_str(line).map_err(|e| e.to_string())
+}
+
+pub fn ok() -> String {
+    json!({ "ok": true }).to_string()
+}
+
+pub fn error(message: &str) -> String {
+    json!({ "ok": false, "error": message }).to_string()
+}
+
+pub fn state(state: &State) -> String {
+    json!({ "ok": true, "state": state }).to_string()
+}
+
+/// Reply to `await-selection`; `null` when the window was closed without one.
+pub fn selection(selection: Option<&str>) -> String {
+    json!({ "ok": true, "selection": selection }).to_string()
+}
+
+const USAGE: &str = "\
+Usage: ctrl-space-wsl msg <command>
+
+Commands:
+  show                 Show the window, or raise it
+  hide                 Hide the window (closes it unless running as a daemon)
+  set-query QUERY      Replace the query
+  set-items [ITEM...]  Replace the items with ITEMs, or lines from stdin;
+                       a selection is then reported instead of launched
+  get-state            Print the current state as JSON
+  await-selection      Wait for the window to close and print the selection";
+
+fn build_request(args: &[String]) -> Result<Value, String> {
+    let Some(command) = args.first() else {
+        return Err(USAGE.to_string());
+    };
+    let rest = &args[1..];
+    let request = match command.as_str() {
+        "show" | "hide" | "get-state" | "await-selection" if rest.is_empty() => {
+            json!({ "command": command })
+        }
+        "set-query" => json!({ "command": command, "query": rest.join(" ") }),
+        "set-items" => {
+            let items: Vec<String> = if rest.is_empty() {
+                io::stdin()
+                    .lock()
+                    .lines()
+                    .map_while(Result::ok)
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            } else {
+                rest.to_vec()
+            };
+            json!({ "command": command, "items": items })
+        }
+        "-h" | "--help" | "help" => return Err(USAGE.to_string()),
+        _ => return Err(format!("invalid message '{}'\n\n{}", args.join(" "), USAGE)),
+    };
+    Ok(request)
+}
+
+fn send(request: &Value) -> io::Result<Value> {
+    let stream = lock::connect()
+        .map_err(|e| io::Error::new(e.kind(), format!("no running instance to talk to ({})", e)))?;
+    // Only await-selection may take as long as the user does
+    if request["command"] != "await-selection" {
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    }
+    writeln!(&stream, "{}", request)?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    serde_json::from_str(&reply).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// `ctrl-space-wsl msg ...`: send one message and print the answer.
+/// Returns the exit code.
+pub fn run_client(args: &[String]) -> i32 {
+    let request = match build_request(args) {
+        Ok(request) => request,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    let reply = match send(&request) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 1;
+        }
+    };
+    if reply["ok"] != true {
+        eprintln!(
+            "Error: {}",
+            reply["error"].as_str().unwrap_or("request failed")
+        );
+        return 1;
+    }
+    if let Some(state) = reply.get("state") {
+        println!("{}", state);
+    }
+    match reply.get("selection") {
+        Some(Value::String(selection)) => println!("{}", selection),
+        // Cancelled, like dmenu
+        Some(_) => return 1,
+        None => {}
+    }
+    0
+}
@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use crate::config::LaunchRule;
+
+/// Match `text` against a shell glob supporting `*`, `?` and `[...]` classes.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    glob_match_at(&pattern, &text)
+}
+
+fn glob_match_at(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| glob_match_at(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && glob_match_at(&pattern[1..], &text[1..]),
+        Some('[') => {
+            let Some(&c) = text.first() else {
+                return false;
+            };
+            match match_class(&pattern[1..], c) {
+                Some((true, rest)) => glob_match_at(rest, &text[1..]),
+                Some((false, _)) => false,
+                // Unterminated class, treat '[' literally
+                None => c == '[' && glob_match_at(&pattern[1..], &text[1..]),
+            }
+        }
+        Some(&p) => text.first() == Some(&p) && glob_match_at(&pattern[1..], &text[1..]),
+    }
+}
+
+/// Match `c` against the class following '['. Returns whether it matched
+/// and the pattern after the closing ']'.
+fn match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negate, mut i) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    let mut matched = false;
+    let mut first = true;
+    while i < pattern.len() {
+        let p = pattern[i];
+        if p == ']' && !first {
+            return Some((matched != negate, &pattern[i + 1..]));
+        }
+        first = false;
+        if i + 2 < pattern.len() && pattern[i + 1] == '-' && pattern[i + 2] != ']' {
+            matched |= (p..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= p == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+/// Find the first rule matching `program`. Patterns containing a slash are
+/// matched against the program as typed, others against its file name.
+pub fn find_rule<'a>(rules: &'a [LaunchRule], program: &str) -> Option<&'a LaunchRule> {
+    let name = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| program.to_string());
+    rules.iter().find(|rule| {
+        if rule.name.contains('/') {
+            glob_match(&rule.name, program)
+        } else {
+            glob_match(&rule.name, &name)
+        }
+    })
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitError {
+    UnterminatedSingleQuote,
+    UnterminatedDoubleQuote,
+    TrailingBackslash,
+}
+
+impl fmt::Display for SplitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SplitError::UnterminatedSingleQuote => write!(f, "unterminated single quote"),
+            SplitError::UnterminatedDoubleQuote => write!(f, "unterminated double quote"),
+            SplitError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+impl std::error::Error for SplitError {}
+
+/// A word of a command line, with whether it starts with an unquoted `~`
+/// or `~/`, which the shell would expand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    pub text: String,
+    pub tilde: bool,
+}
+
+impl Word {
+    /// The word with its leading `~` expanded to `home`, if unquoted.
+    pub fn expand(&self, home: &str) -> String {
+        if self.tilde {
+            expand_tilde(&self.text, home)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+/// Split a command line into words following POSIX shell quoting rules.
+/// Only quoting is interpreted: no expansions, globbing or operators, so
+/// `;`, `|` and `$(...)` end up as literal characters of a word.
+pub fn split(input: &str) -> Result<Vec<String>, SplitError> {
+    Ok(split_words(input)?.into_iter().map(|w| w.text).collect())
+}
+
+/// Like `split`, but noting which words start with an unquoted `~`.
+pub fn split_words(input: &str) -> Result<Vec<Word>, SplitError> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Distinguishes an empty quoted word ("") from no word at all
+    let mut in_word = false;
+    // Whether the word so far is an unquoted `~` or `~/`
+    let mut tilde = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        // Only `~` itself or followed by an unquoted `/` is expanded
+        if in_word && word == "~" && !matches!(c, ' ' | '\t' | '\n') {
+            tilde &= c == '/';
+        }
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_word {
+                    words.push(Word {
+                        text: std::mem::take(&mut word),
+                        tilde,
+                    });
+                    in_word = false;
+                    tilde = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedSingleQuote),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('$' | '`' | '"' | '\\')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(SplitError::UnterminatedDoubleQuote),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(SplitError::UnterminatedDoubleQuote),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(c) => {
+                    in_word = true;
+                    word.push(c);
+                }
+                None => return Err(SplitError::TrailingBackslash),
+            },
+            c => {
+                if !in_word {
+                    tilde = c == '~';
+                }
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(Word { text: word, tilde });
+    }
+    Ok(words)
+}
+
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c)
+}
+
+/// Quote a single word so that `split` gives it back unchanged.
+/// Words made only of safe characters are returned as is.
+pub fn quote(word: &str) -> String {
+    if !word.is_empty() && word.chars().all(is_safe) {
+        return word.to_string();
+    }
+    format!("'{}'", word.replace('\'', r"'\''"))
+}
+
+/// Quote a word for the canonical command line, leaving a leading `~` to
+/// expand unquoted.
+pub fn quote_word(word: &Word) -> String {
+    if !word.tilde {
+        return quote(&word.text);
+    }
+    match word.text.strip_prefix("~/") {
+        Some("") => "~/".to_string(),
+        Some(rest) => format!("~/{}", quote(rest)),
+        None => "~".to_string(),
+    }
+}
+
+/// Join words into the canonical quoted command line.
+pub fn join<S: AsRef<str>>(words: &[S]) -> String {
+    words
+        .iter()
+        .map(|w| quote(w.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Expand a leading `~` or `~/` to `home`, like an unquoted shell word.
+pub fn expand_tilde(word: &str, home: &str) -> String {
+    if word == "~" {
+        return home.to_string();
+    }
+    match word.strip_prefix("~/") {
+        Some(rest) => format!("{}/{}", home.trim_end_matches('/'), rest),
+        None => word.to_string(),
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::ffi::{CString, OsStr, OsString};
+use std::fs::File;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::AsRawFd;
+use std::process::Command;
+
+// Messages sent over the status pipe, each one atomic write of two i32s.
+const MSG_PID: i32 = 1;
+const MSG_ERRNO: i32 = 2;
+
+fn cstring(s: &OsStr) -> io::Result<CString> {
+    CString::new(s.as_bytes()).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul byte"))
+}
+
+fn errno() -> i32 {
+    io::Error::last_os_error()
+        .raw_os_error()
+        .unwrap_or(libc::EIO)
+}
+
+/// Write a status message. Only async-signal-safe calls, runs after fork.
+unsafe fn report(fd: i32, tag: i32, value: i32) {
+    let msg = [tag, value];
+    libc::write(fd, msg.as_ptr() as *const libc::c_void, 8);
+}
+
+fn read_messages(fd: i32) -> Vec<(i32, i32)> {
+    let mut messages = Vec::new();
+    let mut msg = [0i32; 2];
+    loop {
+        let n = unsafe { libc::read(fd, msg.as_mut_ptr() as *mut libc::c_void, 8) };
+        if n == 8 {
+            messages.push((msg[0], msg[1]));
+        } else if n < 0 && errno() == libc::EINTR {
+            continue;
+        } else {
+            // EOF: every write end is closed, either by exit or by a successful exec
+            break;
+        }
+    }
+    messages
+}
+
+/// Spawn `cmd` as a daemon: double fork, new session, stdio on /dev/null.
+///
+/// Program, arguments, environment and working directory are taken from
+/// `cmd`. A close-on-exec pipe reports back whether `execvp` succeeded, so
+/// a missing program or bad working directory surfaces as an error here
+/// instead of disappearing in the background. Returns the daemon's pid.
+pub fn spawn_detached(cmd: &Command) -> io::Result<u32> {
+    spawn(cmd, true, None)
+}
+
+/// Like `spawn_detached`, but with a single fork so the process stays our
+/// child and can be waited for, and with stderr going to `stderr`.
+pub fn spawn_supervised(cmd: &Command, stderr: &File) -> io::Result<u32> {
+    spawn(cmd, false, Some(stderr.as_raw_fd()))
+}
+
+fn spawn(cmd: &Command, double_fork: bool, stderr_fd: Option<i32>) -> io::Result<u32> {
+    let program = cstring(cmd.get_program())?;
+    let mut argv = vec![program.clone()];
+    for arg in cmd.get_args() {
+        argv.push(cstring(arg)?);
+    }
+    let mut argv_ptrs: Vec<*const libc::c_char> = argv.iter().map(|a| a.as_ptr()).collect();
+    argv_ptrs.push(std::ptr::null());
+
+    let mut vars: BTreeMap<OsString, OsString> = std::env::vars_os().collect();
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => vars.insert(key.to_os_string(), value.to_os_string()),
+            None => vars.remove(key),
+        };
+    }
+    let mut envp = Vec::with_capacity(vars.len());
+    for (key, value) in vars {
+        let mut entry = key.into_vec();
+        entry.push(b'=');
+        entry.extend(value.into_vec());
+        envp.push(cstring(OsStr::from_bytes(&entry))?);
+    }
+    let mut envp_ptrs: Vec<*const libc::c_char> = envp.iter().map(|e| e.as_ptr()).collect();
+    envp_ptrs.push(std::ptr::null());
+
+    let cwd = cmd
+        .get_current_dir()
+        .map(|dir| cstring(dir.as_os_str()))
+        .transpose()?;
+
+    let null_fd = unsafe { libc::open(c"/dev/null".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+    if null_fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut fds = [0i32; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        let err = io::Error::last_os_error();
+        unsafe { libc::close(null_fd) };
+        return Err(err);
+    }
+    let (read_fd, write_fd) = (fds[0], fds[1]);
+
+    // Runs in the process that execs: only async-signal-safe calls from here.
+    let exec = || unsafe {
+        libc::dup2(null_fd, 0);
+        libc::dup2(null_fd, 1);
+        libc::dup2(stderr_fd.unwrap_or(null_fd), 2);
+        if let Some(ref cwd) = cwd {
+            if libc::chdir(cwd.as_ptr()) < 0 {
+                report(write_fd, MSG_ERRNO, errno());
+                libc::_exit(127);
+            }
+        }
+        // Undo what the Rust runtime set up for us, like std::process does:
+        // SIGPIPE is ignored and that would be inherited across exec
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+        let mut empty: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut empty);
+        libc::sigprocmask(libc::SIG_SETMASK, &empty, std::ptr::null_mut());
+        libc::execvpe(program.as_ptr(), argv_ptrs.as_ptr(), envp_ptrs.as_ptr());
+        report(write_fd, MSG_ERRNO, errno());
+        libc::_exit(127);
+    };
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        let err = io::Error::last_os_error();
+        unsafe {
+            libc::close(read_fd);
+            libc::close(write_fd);
+            libc::close(null_fd);
+        }
+        return Err(err);
+    }
+
+    if pid == 0 {
+        unsafe {
+            libc::close(read_fd);
+            if libc::setsid() < 0 {
+                report(write_fd, MSG_ERRNO, errno());
+                libc::_exit(1);
+            }
+            if !double_fork {
+                exec();
+            }
+            // Intermediate child: fork the daemon and exit so it gets
+            // reparented and never becomes our zombie.
+            let daemon = libc::fork();
+            if daemon < 0 {
+                report(write_fd, MSG_ERRNO, errno());
+                libc::_exit(1);
+            }
+            if daemon == 0 {
+                exec();
+            }
+            report(write_fd, MSG_PID, daemon);
+            libc::_exit(0);
+        }
+    }
+
+    unsafe {
+        libc::close(write_fd);
+        libc::close(null_fd);
+    }
+
+    let mut status = 0;
+    if double_fork {
+        while unsafe { libc::waitpid(pid, &mut status, 0) } < 0 && errno() == libc::EINTR {}
+    }
+
+    let messages = read_messages(read_fd);
+    unsafe { libc::close(read_fd) };
+
+    let mut child_pid = (!double_fork).then_some(pid as u32);
+    for (tag, value) in messages {
+        match tag {
+            MSG_ERRNO => {
+                if !double_fork {
+                    // Reap the failed child
+                    while unsafe { libc::waitpid(pid, &mut status, 0) } < 0
+                        && errno() == libc::EINTR
+                    {}
+                }
+                return Err(io::Error::from_raw_os_error(value));
+            }
+            MSG_PID => child_pid = Some(value as u32),
+            _ => {}
+        }
+    }
+    child_pid.ok_or_else(|| io::Error::other("daemon process was not started"))
+}
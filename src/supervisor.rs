@@ -0,0 +1,170 @@
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::config::{state_dir, Config};
+use crate::shell;
+use crate::spawn::spawn_detached;
+
+/// Per-launch logs kept in the launches directory.
+const MAX_LOGS: usize = 20;
+/// Lines of stderr included in the notification.
+const TAIL_LINES: usize = 5;
+
+static WATCHERS: Mutex<Vec<JoinHandle<()>>> = Mutex::new(Vec::new());
+
+fn logs_dir() -> PathBuf {
+    state_dir().join("launches")
+}
+
+/// Delete the oldest per-launch logs so at most `MAX_LOGS - 1` remain.
+fn prune_logs(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    // Names start with the launch time, so lexical order is chronological
+    logs.sort();
+    let excess = (logs.len() + 1).saturating_sub(MAX_LOGS);
+    for path in logs.into_iter().take(excess) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Create the stderr log for a launch of `program`.
+pub fn create_log(program: &str) -> std::io::Result<(File, PathBuf)> {
+    let dir = logs_dir();
+    fs::create_dir_all(&dir)?;
+    prune_logs(&dir);
+    let name: String = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = dir.join(format!("{}-{}.log", stamp, name));
+    Ok((File::create(&path)?, path))
+}
+
+fn tail(path: &Path, lines: usize) -> String {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let all: Vec<&str> = content.lines().filter(|l| !l.trim().is_empty()).collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+fn notify(config: &Config, title: &str, body: &str) {
+    let mut argv = if config.launcher.notify_command.trim().is_empty() {
+        vec!["notify-send".to_string()]
+    } else {
+        match shell::split(&config.launcher.notify_command) {
+            Ok(argv) if !argv.is_empty() => argv,
+            _ => {
+                warn!("supervise: invalid notify_command");
+                return;
+            }
+        }
+    };
+    argv.push(title.to_string());
+    argv.push(body.to_string());
+    let mut cmd = Command::new(&argv[0]);
+    cmd.args(&argv[1..]);
+    if let Err(e) = spawn_detached(&cmd) {
+        warn!("supervise: notify failed: {}", e);
+    }
+}
+
+/// Reap `pid` whenever it exits, on a thread of its own that nothing waits
+/// for, so a long-running app doesn't stay a zombie in a daemon.
+pub fn reap_later(pid: u32) {
+    std::thread::spawn(move || {
+        let mut status = 0;
+        unsafe { libc::waitpid(pid as i32, &mut status, 0) };
+        debug!("supervise: reaped {}", pid);
+    });
+}
+
+/// Wait up to the grace period for `pid` (our child) to exit. If it exits
+/// unsuccessfully, send a notification with the tail of its stderr.
+fn supervise(pid: u32, command: &str, log_path: &Path, config: &Config) {
+    let grace = Duration::from_millis(config.launcher.supervise_grace_ms);
+    let deadline = Instant::now() + grace;
+    let mut status = 0;
+    loop {
+        let ret = unsafe { libc::waitpid(pid as i32, &mut status, libc::WNOHANG) };
+        if ret == pid as i32 {
+            break;
+        }
+        if ret < 0 {
+            // Not ours to wait for
+            return;
+        }
+        if Instant::now() >= deadline {
+            // Still running: no more notifications, but it still needs reaping
+            reap_later(pid);
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    let outcome = if libc::WIFEXITED(status) {
+        match libc::WEXITSTATUS(status) {
+            0 => {
+                debug!("supervise: {} exited successfully", command);
+                return;
+            }
+            code => format!("exited with status {}", code),
+        }
+    } else if libc::WIFSIGNALED(status) {
+        format!("was killed by signal {}", libc::WTERMSIG(status))
+    } else {
+        return;
+    };
+
+    warn!(
+        "supervise: {} {} (log: {})",
+        command,
+        outcome,
+        log_path.display()
+    );
+    let mut body = tail(log_path, TAIL_LINES);
+    if body.is_empty() {
+        body = format!("No output, see {}", log_path.display());
+    }
+    notify(config, &format!("{} {}", command, outcome), &body);
+}
+
+/// Supervise a launched child on a background thread.
+pub fn watch(pid: u32, command: String, log_path: PathBuf, config: &Config) {
+    let config = config.clone();
+    let handle = std::thread::spawn(move || supervise(pid, &command, &log_path, &config));
+    if let Ok(mut watchers) = WATCHERS.lock() {
+        watchers.retain(|handle| !handle.is_finished());
+        watchers.push(handle);
+    }
+}
+
+/// Block until every supervised child has exited or outlived its grace
+/// period. Called once the launcher window is closed.
+pub fn wait_all() {
+    let handles = match WATCHERS.lock() {
+        Ok(mut watchers) => std::mem::take(&mut *watchers),
+        Err(_) => return,
+    };
+    for handle in handles {
+        let _ = handle.join();
+    }
+}
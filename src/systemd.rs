@@ -0,0 +1,204 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::app_discovery::find_executable;
+use crate::config::SystemdScope;
+
+static SCOPE_COUNTER: AtomicU32 = AtomicU32::new(0);
+/// How long systemd-run gets to set up the scope before the launch is taken
+/// to have worked.
+const START_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(format!("/run/user/{}", unsafe { libc::getuid() })))
+}
+
+/// Whether `systemd-run --user` can work: the binary exists and a user
+/// manager is listening. Checked once per process.
+pub fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let manager = runtime_dir().join("systemd/private").exists();
+        let available = manager && find_executable("systemd-run").is_some();
+        debug!(
+            "systemd user manager {}",
+            if available {
+                "available"
+            } else {
+                "unavailable"
+            }
+        );
+        available
+    })
+}
+
+/// Escape a string for use in a unit name, like `systemd-escape`.
+fn escape(name: &str) -> String {
+    let mut out = String::new();
+    for (i, b) in name.bytes().enumerate() {
+        let plain = b.is_ascii_alphanumeric() || b == b':' || b == b'_' || (b == b'.' && i > 0);
+        if plain {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+    }
+    out
+}
+
+/// Unit name following the `app-<launcher>-<app>-<random>.scope` convention,
+/// with `<pid>-<counter>` as the unique part.
+pub fn unit_name(program: &str) -> String {
+    let app = Path::new(program)
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| program.to_string());
+    format!(
+        "app-ctrl\\x2dspace\\x2dwsl-{}-{}-{}.scope",
+        escape(&app),
+        std::process::id(),
+        SCOPE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Whether `value` is a `MemoryMax=` systemd accepts: bytes with an optional
+/// K, M, G, T, P or E suffix, a percentage, or `infinity`.
+fn valid_memory_max(value: &str) -> bool {
+    if value == "infinity" {
+        return true;
+    }
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent
+            .parse::<f64>()
+            .is_ok_and(|p| (0.0..=100.0).contains(&p));
+    }
+    let number = value
+        .strip_suffix(['K', 'M', 'G', 'T', 'P', 'E'])
+        .unwrap_or(value);
+    !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && number.parse::<f64>().is_ok()
+}
+
+/// Problems with the resource controls of `[launcher.systemd]`, as
+/// (key, message) pairs. systemd-run would refuse to create the scope.
+pub fn check(scope: &SystemdScope) -> Vec<(&'static str, String)> {
+    let mut problems = Vec::new();
+    if let Some(value) = scope.memory_max.as_deref().filter(|v| !valid_memory_max(v)) {
+        problems.push((
+            "launcher.systemd.memory_max",
+            format!(
+                "invalid memory limit '{}', expected e.g. 512M, 4G, 50% or infinity",
+                value
+            ),
+        ));
+    }
+    if let Some(weight) = scope.cpu_weight.filter(|w| !(1..=10000).contains(w)) {
+        problems.push((
+            "launcher.systemd.cpu_weight",
+            format!("cpu weight {} out of range 1..=10000", weight),
+        ));
+    }
+    if let Some(nice) = scope.nice.filter(|n| !(-20..=19).contains(n)) {
+        problems.push((
+            "launcher.systemd.nice",
+            format!("nice level {} out of range -20..=19", nice),
+        ));
+    }
+    problems
+}
+
+/// Wrap `cmd` in `systemd-run --user --scope` with the configured resource
+/// controls, keeping its environment and working directory.
+///
+/// systemd-run execs the program itself once the scope exists, so a missing
+/// program would only be noticed after our exec succeeded; it is looked up
+/// here instead to keep reporting it as a launch error. Resource controls
+/// systemd would refuse are an `InvalidInput` error.
+pub fn scope_command(cmd: &Command, scope: &SystemdScope) -> io::Result<Command> {
+    if let Some((key, message)) = check(scope).into_iter().next() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{}: {}", key, message),
+        ));
+    }
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    // Relative paths are resolved in the child's working directory
+    let lookup = match cmd.get_current_dir() {
+        Some(dir) if program.contains('/') => dir.join(&program).to_string_lossy().into_owned(),
+        _ => program.clone(),
+    };
+    if find_executable(&lookup).is_none() {
+        return Err(io::Error::from_raw_os_error(libc::ENOENT));
+    }
+
+    let mut wrapped = Command::new("systemd-run");
+    wrapped.args(["--user", "--scope", "--quiet", "--collect"]);
+    wrapped.arg(format!("--unit={}", unit_name(&program)));
+    if let Some(ref memory_max) = scope.memory_max {
+        wrapped.arg(format!("--property=MemoryMax={}", memory_max));
+    }
+    if let Some(cpu_weight) = scope.cpu_weight {
+        wrapped.arg(format!("--property=CPUWeight={}", cpu_weight));
+    }
+    if let Some(nice) = scope.nice {
+        wrapped.arg(format!("--nice={}", nice));
+    }
+    wrapped.arg("--");
+    wrapped.arg(&program);
+    wrapped.args(cmd.get_args());
+    for (key, value) in cmd.get_envs() {
+        match value {
+            Some(value) => wrapped.env(key, value),
+            None => wrapped.env_remove(key),
+        };
+    }
+    if let Some(dir) = cmd.get_current_dir() {
+        wrapped.current_dir(dir);
+    }
+    Ok(wrapped)
+}
+
+/// Name of process `pid`, which is still there for a zombie.
+fn comm(pid: u32) -> Option<String> {
+    let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+    Some(comm.trim_end().to_string())
+}
+
+/// Wait for systemd-run, our child `pid`, to exec the program, which it does
+/// once the scope exists. Returns false, with the child reaped, if it exited
+/// first: the scope couldn't be created and the program never started.
+pub fn started(pid: u32) -> bool {
+    let deadline = Instant::now() + START_TIMEOUT;
+    loop {
+        // WNOWAIT leaves an exited program to whoever watches it
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+        if unsafe { libc::waitid(libc::P_PID, pid, &mut info, flags) } < 0 {
+            if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            // Not ours to wait for, so there is nothing to tell
+            return true;
+        }
+        let exited = unsafe { info.si_pid() } != 0;
+        if comm(pid).is_some_and(|name| name != "systemd-run") {
+            return true;
+        }
+        if exited {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid as i32, &mut status, 0) };
+            return false;
+        }
+        if Instant::now() >= deadline {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::app_discovery::find_executable;
+use crate::config::DEFAULT_TERMINAL;
+use crate::shell;
+use crate::wslpath;
+
+const PLACEHOLDERS: &[&str] = &["{cmd}", "{argv}", "{cwd}", "{title}"];
+
+/// Known terminals, in order of preference, with their command templates.
+const KNOWN_TERMINALS: &[(&str, &str)] = &[
+    (
+        "alacritty",
+        "alacritty --working-directory {cwd} --title {title} -e {argv}",
+    ),
+    ("kitty", "kitty --directory {cwd} --title {title} {argv}"),
+    (
+        "foot",
+        "foot --working-directory={cwd} --title={title} {argv}",
+    ),
+    ("wezterm", "wezterm start --cwd {cwd} -- {argv}"),
+];
+
+/// Find an installed terminal, falling back to Windows Terminal on WSL.
+pub fn detect() -> Option<String> {
+    for (program, template) in KNOWN_TERMINALS {
+        if find_executable(program).is_some() {
+            return Some(template.to_string());
+        }
+    }
+    if wslpath::is_wsl() && find_executable("wt.exe").is_some() {
+        let distro = std::env::var("WSL_DISTRO_NAME")
+            .map(|d| format!("-d {} ", shell::quote(&d)))
+            .unwrap_or_default();
+        return Some(format!(
+            "wt.exe --title {{title}} wsl.exe {}--cd {{cwd}} -- {{argv}}",
+            distro
+        ));
+    }
+    None
+}
+
+/// The template to use for `configured`: "auto" (or empty) always detects,
+/// and the default `x-terminal-emulator` is replaced by a detected terminal
+/// when it is not installed.
+pub fn resolve(configured: &str) -> String {
+    let configured = configured.trim();
+    let wants_detection = configured.is_empty()
+        || configured == "auto"
+        || (configured == DEFAULT_TERMINAL && find_executable("x-terminal-emulator").is_none());
+    if wants_detection {
+        if let Some(template) = detect() {
+            debug!("terminal: detected {:?}", template);
+            return template;
+        }
+    }
+    configured.to_string()
+}
+
+/// Replace each placeholder in `word` with its value in one left-to-right
+/// pass, so braces inside a value, e.g. in a title, are left as they are.
+fn substitute(word: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::new();
+    let mut rest = word;
+    while !rest.is_empty() {
+        match values.iter().find(|(p, _)| rest.starts_with(p)) {
+            Some((placeholder, value)) => {
+                out.push_str(value);
+                rest = &rest[placeholder.len()..];
+            }
+            None => {
+                let c = rest.chars().next().unwrap_or_default();
+                out.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    out
+}
+
+/// Build the terminal command running `argv` in `cwd`.
+///
+/// Templates may use `{argv}` (the command as separate arguments), `{cmd}`
+/// (the command as one quoted string), `{cwd}` and `{title}`. A template
+/// without placeholders gets the command arguments appended.
+pub fn command(
+    template: &str,
+    argv: &[String],
+    cwd: &Path,
+    title: &str,
+) -> Result<Command, String> {
+    let words = match shell::split(template) {
+        Ok(words) if !words.is_empty() => words,
+        _ => return Err(format!("invalid terminal command {:?}", template)),
+    };
+
+    let mut expanded = Vec::new();
+    if !words
+        .iter()
+        .any(|w| PLACEHOLDERS.iter().any(|p| w.contains(p)))
+    {
+        expanded = words;
+        expanded.extend(argv.iter().cloned());
+    } else {
+        let is_windows_terminal = words[0].to_lowercase().ends_with(".exe");
+        let cwd = if is_windows_terminal {
+            wslpath::to_windows(cwd)
+        } else {
+            None
+        }
+        .unwrap_or_else(|| cwd.to_string_lossy().into_owned());
+        let cmd = shell::join(argv);
+        for word in words {
+            if word == "{argv}" {
+                expanded.extend(argv.iter().cloned());
+            } else {
+                let values = [("{cmd}", cmd.as_str()), ("{cwd}", &cwd), ("{title}", title)];
+                expanded.push(substitute(&word, &values));
+            }
+        }
+    }
+
+    let mut cmd = Command::new(&expanded[0]);
+    cmd.args(&expanded[1..]);
+    cmd.current_dir(cwd);
+    Ok(cmd)
+}
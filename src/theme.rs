@@ -0,0 +1,184 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::config::config_dir;
+
+pub const DEFAULT_THEME: &str = "dracula";
+
+/// The six colours of the bar, as colour strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub foreground: String,
+    pub background: String,
+    pub selection_fg: String,
+    pub selection_bg: String,
+    pub match_highlight: String,
+    pub prompt_color: String,
+}
+
+impl Theme {
+    fn from_hex(colors: [&str; 6]) -> Self {
+        let [foreground, background, selection_fg, selection_bg, match_highlight, prompt_color] =
+            colors.map(str::to_string);
+        Self {
+            foreground,
+            background,
+            selection_fg,
+            selection_bg,
+            match_highlight,
+            prompt_color,
+        }
+    }
+
+    /// Replace colours that are given, in the order of `entries`.
+    pub fn with_overrides(mut self, overrides: [Option<&str>; 6]) -> Self {
+        let slots = [
+            &mut self.foreground,
+            &mut self.background,
+            &mut self.selection_fg,
+            &mut self.selection_bg,
+            &mut self.match_highlight,
+            &mut self.prompt_color,
+        ];
+        for (slot, value) in slots.into_iter().zip(overrides) {
+            if let Some(value) = value {
+                *slot = value.to_string();
+            }
+        }
+        self
+    }
+
+    /// Colours by config key, for validation.
+    pub fn entries(&self) -> [(&'static str, &str); 6] {
+        [
+            ("foreground", &self.foreground),
+            ("background", &self.background),
+            ("selection_fg", &self.selection_fg),
+            ("selection_bg", &self.selection_bg),
+            ("match_highlight", &self.match_highlight),
+            ("prompt_color", &self.prompt_color),
+        ]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        bundled(DEFAULT_THEME).expect("default theme is bundled")
+    }
+}
+
+/// Bundled presets: foreground, background, selection fg and bg, match
+/// highlight and prompt colour.
+const BUNDLED: &[(&str, [&str; 6])] = &[
+    (
+        "catppuccin",
+        [
+            "#cdd6f4", "#1e1e2e", "#cdd6f4", "#45475a", "#89dceb", "#cba6f7",
+        ],
+    ),
+    (
+        "dracula",
+        [
+            "#f8f8f2", "#21222c", "#f8f8f2", "#6272a4", "#8be9fd", "#bd93f9",
+        ],
+    ),
+    (
+        "gruvbox",
+        [
+            "#ebdbb2", "#282828", "#fbf1c7", "#504945", "#fabd2f", "#fe8019",
+        ],
+    ),
+    (
+        "nord",
+        [
+            "#d8dee9", "#2e3440", "#eceff4", "#434c5e", "#88c0d0", "#81a1c1",
+        ],
+    ),
+    (
+        "solarized-dark",
+        [
+            "#839496", "#002b36", "#93a1a1", "#073642", "#b58900", "#268bd2",
+        ],
+    ),
+    (
+        "solarized-light",
+        [
+            "#657b83", "#fdf6e3", "#586e75", "#eee8d5", "#b58900", "#268bd2",
+        ],
+    ),
+];
+
+fn bundled(name: &str) -> Option<Theme> {
+    BUNDLED
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, colors)| Theme::from_hex(*colors))
+}
+
+/// A user theme file; colours it leaves out come from the default theme.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    foreground: Option<String>,
+    background: Option<String>,
+    selection_fg: Option<String>,
+    selection_bg: Option<String>,
+    match_highlight: Option<String>,
+    prompt_color: Option<String>,
+}
+
+pub fn themes_dir() -> PathBuf {
+    config_dir().join("themes")
+}
+
+fn user_theme_path(name: &str) -> PathBuf {
+    themes_dir().join(format!("{}.toml", name))
+}
+
+/// Load a theme by name. A file `themes/<name>.toml` next to the config
+/// takes precedence over the bundled preset of the same name.
+pub fn load(name: &str) -> Result<Theme, String> {
+    let path = user_theme_path(name);
+    if path.is_file() {
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let file: ThemeFile = toml::from_str(&content)
+            .map_err(|e: toml::de::Error| format!("{}: {}", path.display(), e.message()))?;
+        let theme = Theme::default().with_overrides([
+            file.foreground.as_deref(),
+            file.background.as_deref(),
+            file.selection_fg.as_deref(),
+            file.selection_bg.as_deref(),
+            file.match_highlight.as_deref(),
+            file.prompt_color.as_deref(),
+        ]);
+        debug!("theme '{}' from {}", name, path.display());
+        return Ok(theme);
+    }
+    bundled(name).ok_or_else(|| format!("unknown theme '{}'", name))
+}
+
+/// Names of all available themes with where they come from, sorted.
+pub fn list() -> Vec<(String, String)> {
+    let mut themes: Vec<(String, String)> = BUNDLED
+        .iter()
+        .map(|(name, _)| (name.to_string(), "bundled".to_string()))
+        .collect();
+    if let Ok(entries) = fs::read_dir(themes_dir()) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|ext| ext != "toml") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            themes.retain(|(n, _)| n != name);
+            themes.push((name.to_string(), path.display().to_string()));
+        }
+    }
+    themes.sort();
+    themes
+}
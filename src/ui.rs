@@ -1,11 +1,21 @@
 use std::collections::HashMap;
 use std::fs;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
 use std::process::Command;
+use std::time::{Duration, Instant};
 
-use crate::config::{parse_hex_color, Config, Rgb};
+use crate::check;
+use crate::color::{parse_color, Rgb};
+use crate::config::{self, Appearance, Config};
 use crate::filter::{filter_apps, FilteredApp};
 use crate::frequency::Frequency;
-use crate::launcher;
+use crate::keys::{keysym, Action, Bindings};
+use crate::launcher::{self, TerminalMode};
+use crate::lock;
+use crate::remote::{self, Request};
+use crate::watch::FileWatcher;
 
 use x11rb::connection::Connection;
 use x11rb::protocol::randr::ConnectionExt as RandrConnectionExt;
@@ -15,27 +25,12 @@ use x11rb::wrapper::ConnectionExt as _;
 
 use fontdue::{Font, FontSettings};
 
-mod keysym {
-    pub const BACKSPACE: u32 = 0xff08;
-    pub const TAB: u32 = 0xff09;
-    pub const RETURN: u32 = 0xff0d;
-    pub const ESCAPE: u32 = 0xff1b;
-    pub const DELETE: u32 = 0xffff;
-    pub const LEFT: u32 = 0xff51;
-    pub const RIGHT: u32 = 0xff53;
-    pub const KP_ENTER: u32 = 0xff8d;
-    pub const V_LOWER: u32 = 0x76;
-    pub const V_UPPER: u32 = 0x56;
-    pub const Y_LOWER: u32 = 0x79;
-    pub const Y_UPPER: u32 = 0x59;
-    pub const Z_LOWER: u32 = 0x7a;
-    pub const Z_UPPER: u32 = 0x5a;
-}
+/// How stale the app list may get in daemon mode before it is rediscovered.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 enum KeyAction {
     None,
     Quit,
-    QuitWithDelay,
     Paste,
 }
 
@@ -128,6 +123,34 @@ struct CachedColors {
     prompt: Rgb,
 }
 
+impl CachedColors {
+    fn new(appearance: &Appearance) -> Self {
+        let theme = appearance.colors();
+        Self {
+            bg: parse_color(&theme.background).unwrap_or(Rgb(33, 34, 44, 255)),
+            fg: parse_color(&theme.foreground).unwrap_or(Rgb(248, 248, 242, 255)),
+            sel_bg: parse_color(&theme.selection_bg).unwrap_or(Rgb(98, 114, 164, 255)),
+            sel_fg: parse_color(&theme.selection_fg).unwrap_or(Rgb(248, 248, 242, 255)),
+            match_hl: parse_color(&theme.match_highlight).unwrap_or(Rgb(139, 233, 253, 255)),
+            prompt: parse_color(&theme.prompt_color).unwrap_or(Rgb(189, 147, 249, 255)),
+        }
+    }
+}
+
+fn bindings(config: &Config) -> Bindings {
+    let (bindings, errors) = Bindings::new(&config.launcher.hold_key, &config.keys);
+    for (key, message) in errors {
+        warn!("{}: {}", key, message);
+    }
+    bindings
+}
+
+struct LaunchError {
+    selection: String,
+    message: String,
+    can_retry_in_terminal: bool,
+}
+
 struct UndoState {
     query: String,
     cursor_pos: usize,
@@ -145,6 +168,10 @@ struct App {
     page_size: usize,
     cursor_in_results: bool,
     delete_confirm: Option<String>,
+    launch_error: Option<LaunchError>,
+    /// One-line warning shown until the first keypress
+    notice: Option<String>,
+    bindings: Bindings,
     font: Font,
     glyph_cache: HashMap<(char, u32), (fontdue::Metrics, Vec<u8>)>,
     colors: CachedColors,
@@ -153,37 +180,55 @@ struct App {
     undo_stack: Vec<UndoState>,
     redo_stack: Vec<UndoState>,
     filter_mode: bool,
+    /// The selection once made: printed in filter mode, sent to await-selection
     filter_result: Option<String>,
+    /// Regular apps and filter mode, while `set-items` replaced them
+    saved_apps: Option<(Vec<String>, bool)>,
 }
 
-fn resolve_font_path(font_family: &str) -> Option<String> {
-    let cache_dir = crate::config::config_dir();
-    let cache_file = cache_dir.join("font_cache");
+pub fn font_cache_path() -> PathBuf {
+    crate::config::cache_dir().join("font_cache")
+}
+
+pub fn resolve_font_path(font_family: &str) -> Option<String> {
+    let cache_file = font_cache_path();
 
     if let Ok(content) = fs::read_to_string(&cache_file) {
         for line in content.lines() {
             if let Some((family, path)) = line.split_once('\t') {
                 if family == font_family && std::path::Path::new(path).exists() {
+                    debug!("font '{}' -> {} (cached)", font_family, path);
                     return Some(path.to_string());
                 }
             }
         }
     }
 
-    let output = Command::new("fc-match")
+    let output = match Command::new("fc-match")
         .args([font_family, "--format=%{file}"])
         .output()
-        .ok()?;
+    {
+        Ok(output) => output,
+        Err(e) => {
+            error!("cannot run fc-match: {}", e);
+            return None;
+        }
+    };
     if !output.status.success() {
+        warn!("fc-match failed for '{}'", font_family);
         return None;
     }
     let path = String::from_utf8(output.stdout).ok()?;
     let path = path.trim().to_string();
     if path.is_empty() {
+        warn!("fc-match found no file for '{}'", font_family);
         return None;
     }
+    debug!("font '{}' -> {}", font_family, path);
 
-    let _ = fs::create_dir_all(&cache_dir);
+    if let Some(dir) = cache_file.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
     let entry = format!("{}\t{}\n", font_family, path);
     let _ = fs::write(&cache_file, entry);
 
@@ -192,8 +237,20 @@ fn resolve_font_path(font_family: &str) -> Option<String> {
 
 fn load_font(font_family: &str) -> Option<Font> {
     let path = resolve_font_path(font_family)?;
-    let data = fs::read(&path).ok()?;
-    Font::from_bytes(data, FontSettings::default()).ok()
+    let data = match fs::read(&path) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("cannot read font {}: {}", path, e);
+            return None;
+        }
+    };
+    match Font::from_bytes(data, FontSettings::default()) {
+        Ok(font) => Some(font),
+        Err(e) => {
+            error!("cannot load font {}: {}", path, e);
+            None
+        }
+    }
 }
 
 fn downscale(src: &[u8], src_width: u16, src_height: u16, scale: u16) -> Vec<u8> {
@@ -248,15 +305,17 @@ impl App {
         font: Font,
         filter_mode: bool,
     ) -> Self {
-        let colors = CachedColors {
-            bg: parse_hex_color(&config.appearance.background).unwrap_or(Rgb(33, 34, 44)),
-            fg: parse_hex_color(&config.appearance.foreground).unwrap_or(Rgb(248, 248, 242)),
-            sel_bg: parse_hex_color(&config.appearance.selection_bg).unwrap_or(Rgb(98, 114, 164)),
-            sel_fg: parse_hex_color(&config.appearance.selection_fg).unwrap_or(Rgb(248, 248, 242)),
-            match_hl: parse_hex_color(&config.appearance.match_highlight)
-                .unwrap_or(Rgb(139, 233, 253)),
-            prompt: parse_hex_color(&config.appearance.prompt_color).unwrap_or(Rgb(189, 147, 249)),
-        };
+        let colors = CachedColors::new(&config.appearance);
+        let bindings = bindings(&config);
+
+        // The warning names the file; other layers may still have loaded
+        let notice = config.load_warning.as_ref().map(|w| {
+            if config.sources.is_empty() {
+                format!("Config ignored, using defaults: {}", w)
+            } else {
+                format!("Config file skipped, using the others: {}", w)
+            }
+        });
 
         Self {
             config,
@@ -270,6 +329,9 @@ impl App {
             page_size: 0,
             cursor_in_results: false,
             delete_confirm: None,
+            launch_error: None,
+            notice,
+            bindings,
             font,
             glyph_cache: HashMap::new(),
             colors,
@@ -279,6 +341,118 @@ impl App {
             redo_stack: Vec::new(),
             filter_mode,
             filter_result: None,
+            saved_apps: None,
+        }
+    }
+
+    /// Clear the query and everything derived from it, for the next show.
+    fn reset(&mut self) {
+        self.query.clear();
+        self.cursor_pos = 0;
+        self.selected = 0;
+        self.scroll_offset = 0;
+        self.cursor_in_results = false;
+        self.delete_confirm = None;
+        self.launch_error = None;
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.filter_result = None;
+        if let Some((apps, filter_mode)) = self.saved_apps.take() {
+            self.apps = apps;
+            self.filter_mode = filter_mode;
+        }
+    }
+
+    fn set_query(&mut self, query: String) {
+        self.save_undo();
+        self.cursor_pos = query.chars().count();
+        self.query = query;
+        self.cursor_in_results = false;
+        self.selected = 0;
+        self.scroll_offset = 0;
+    }
+
+    /// Offer `items` instead of the apps; picking one reports it instead of
+    /// launching it, as in filter mode.
+    fn set_items(&mut self, items: Vec<String>) {
+        if self.saved_apps.is_none() {
+            self.saved_apps = Some((std::mem::take(&mut self.apps), self.filter_mode));
+        }
+        self.apps = items;
+        self.filter_mode = true;
+        self.selected = 0;
+        self.scroll_offset = 0;
+    }
+
+    fn state(&self, visible: bool) -> remote::State {
+        let results = self.results();
+        remote::State {
+            visible,
+            query: self.query.clone(),
+            selected: results.get(self.selected).map(|r| r.name.clone()),
+            items: self.apps.len(),
+            custom_items: self.saved_apps.is_some(),
+        }
+    }
+
+    /// Pick up apps found by the last background refresh and start another
+    /// one if the list is older than `REFRESH_INTERVAL`.
+    fn refresh_apps(&mut self, last_refresh: &mut Instant) {
+        if self.frequency.take_refreshed() {
+            self.frequency = Frequency::load();
+            match &mut self.saved_apps {
+                Some((apps, _)) => *apps = self.frequency.apps(),
+                None => self.apps = self.frequency.apps(),
+            }
+        }
+        if last_refresh.elapsed() >= REFRESH_INTERVAL {
+            self.frequency.refresh_in_background();
+            *last_refresh = Instant::now();
+        }
+    }
+
+    /// Switch to a reloaded config, keeping the current one if the new one
+    /// failed to parse or names a font that can't be loaded.
+    fn reload_config(&mut self, config: Config) -> Result<(), String> {
+        if let Some(warning) = config.load_warning {
+            return Err(warning);
+        }
+        // Only problems the edit brings in; the current config may have
+        // some of its own, like a terminal that isn't installed
+        let current = check::validate(&self.config);
+        if let Some((key, message)) = check::validate(&config)
+            .into_iter()
+            .find(|problem| !current.contains(problem))
+        {
+            return Err(format!("{}: {}", key, message));
+        }
+        let family = &config.appearance.font_family;
+        if *family != self.config.appearance.font_family {
+            self.font = load_font(family).ok_or_else(|| format!("font '{}' not found", family))?;
+            self.glyph_cache.clear();
+        }
+        self.colors = CachedColors::new(&config.appearance);
+        self.bindings = bindings(&config);
+        self.config = config;
+        Ok(())
+    }
+
+    fn window_height(&self) -> u16 {
+        window_height(&self.config, &self.font)
+    }
+
+    fn results(&self) -> Vec<FilteredApp> {
+        filter_apps(
+            &self.apps,
+            &self.query,
+            &self.frequency,
+            self.config.launcher.ignore_case,
+        )
+    }
+
+    fn save_frequency(&self) {
+        if let Err(e) = self.frequency.save() {
+            warn!("cannot save history: {}", e);
         }
     }
 
@@ -347,7 +521,10 @@ impl App {
             } else {
                 (font_size * 0.8, font_size)
             };
-        let baseline = ((height as f32 - line_height) / 2.0 + line_ascent) as i32;
+        // In list mode the bar is the first of `lines + 1` equal rows
+        let lines = self.config.appearance.lines as i32;
+        let row_height = height as i32 / (lines + 1);
+        let baseline = ((row_height as f32 - line_height) / 2.0 + line_ascent) as i32;
         let x_offset: i32 = 2 * scale_i;
 
         if let Some(ref name) = self.delete_confirm {
@@ -366,12 +543,73 @@ impl App {
             return buffer;
         }
 
-        let results = filter_apps(&self.apps, &self.query, &self.frequency);
+        if let Some(ref notice) = self.notice {
+            let notice = notice.clone();
+            self.draw_text(
+                &mut buffer,
+                width,
+                &notice,
+                x_offset + char_width,
+                baseline,
+                self.colors.prompt,
+                &[],
+                self.colors.prompt,
+                font_size,
+            );
+            return buffer;
+        }
+
+        if let Some(ref error) = self.launch_error {
+            let mut line = format!("Failed to launch '{}': {}", error.selection, error.message);
+            let retry = self.bindings.chords(Action::AcceptInTerminal);
+            if let Some(chord) = retry.first().filter(|_| error.can_retry_in_terminal) {
+                line.push_str(&format!(" ({}: retry in terminal)", chord));
+            }
+            self.draw_text(
+                &mut buffer,
+                width,
+                &line,
+                x_offset + char_width,
+                baseline,
+                self.colors.prompt,
+                &[],
+                self.colors.prompt,
+                font_size,
+            );
+            return buffer;
+        }
+
+        let results = self.results();
         if self.selected >= results.len() {
             self.selected = results.len().saturating_sub(1);
         }
 
-        let text_start = x_offset + char_width;
+        let mut text_start = x_offset + char_width;
+        if !self.config.appearance.prompt.is_empty() {
+            let prompt = self.config.appearance.prompt.clone();
+            let prompt_width = self.measure_text(&prompt, font_size) + 2 * layout.item_pad;
+            self.fill_rect(
+                &mut buffer,
+                width,
+                0,
+                0,
+                text_start - layout.item_pad + prompt_width,
+                row_height,
+                self.colors.sel_bg,
+            );
+            self.draw_text(
+                &mut buffer,
+                width,
+                &prompt,
+                text_start,
+                baseline,
+                self.colors.sel_fg,
+                &[],
+                self.colors.sel_fg,
+                font_size,
+            );
+            text_start += prompt_width;
+        }
         let query = self.query.clone();
         let text_before_cursor: String = query.chars().take(self.cursor_pos).collect();
 
@@ -387,12 +625,12 @@ impl App {
             font_size,
         );
 
-        let cursor_color = Rgb(192, 222, 255);
+        let cursor_color = Rgb(192, 222, 255, 255);
         let cursor_offset = self.measure_text(&text_before_cursor, font_size) - scale_i;
         let cursor_x = (text_start + cursor_offset) / scale_i * scale_i;
         let cursor_y = scale_i;
         // Cursor spans full height minus 1px padding on top and bottom (after downscale)
-        let cursor_height = height as i32 - 2 * scale_i;
+        let cursor_height = row_height - 2 * scale_i;
         self.fill_rect(
             &mut buffer,
             width,
@@ -403,6 +641,11 @@ impl App {
             cursor_color,
         );
 
+        if lines > 0 {
+            self.draw_list(&mut buffer, width, &results, &layout, row_height, baseline);
+            return buffer;
+        }
+
         let mut x_offset = layout.results_x;
 
         let mut visible_count = 0;
@@ -455,6 +698,63 @@ impl App {
         buffer
     }
 
+    /// Draw results one per row below the bar, scrolled to keep the
+    /// selection visible.
+    fn draw_list(
+        &mut self,
+        buffer: &mut [u8],
+        width: u16,
+        results: &[FilteredApp],
+        layout: &ResultsLayout,
+        row_height: i32,
+        baseline: i32,
+    ) {
+        let lines = self.config.appearance.lines as usize;
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset + lines {
+            self.scroll_offset = self.selected + 1 - lines;
+        }
+
+        let mut visible_count = 0;
+        for (i, app) in results
+            .iter()
+            .enumerate()
+            .skip(self.scroll_offset)
+            .take(lines)
+        {
+            let row_y = (visible_count as i32 + 1) * row_height;
+            let text_color = if i == self.selected {
+                self.fill_rect(
+                    buffer,
+                    width,
+                    0,
+                    row_y,
+                    width as i32,
+                    row_height,
+                    self.colors.sel_bg,
+                );
+                self.colors.sel_fg
+            } else {
+                self.colors.fg
+            };
+            self.draw_text(
+                buffer,
+                width,
+                &app.name,
+                layout.char_width + 2 * layout.item_pad,
+                row_y + baseline,
+                text_color,
+                &app.match_indices,
+                self.colors.match_hl,
+                layout.font_size,
+            );
+            self.last_visible = i;
+            visible_count += 1;
+        }
+        self.page_size = visible_count;
+    }
+
     #[allow(clippy::too_many_arguments)]
     fn fill_rect(
         &self,
@@ -471,9 +771,13 @@ impl App {
         for py in y.max(0)..(y + h).min(row_count) {
             for px in x.max(0)..(x + w).min(buf_width as i32) {
                 let idx = (py as usize * buf_width as usize + px as usize) * 4;
-                buffer[idx] = color.2;
-                buffer[idx + 1] = color.1;
-                buffer[idx + 2] = color.0;
+                if color.is_opaque() {
+                    buffer[idx] = color.2;
+                    buffer[idx + 1] = color.1;
+                    buffer[idx + 2] = color.0;
+                } else {
+                    color.blend_over(&mut buffer[idx..idx + 3], 1.0);
+                }
             }
         }
     }
@@ -521,18 +825,13 @@ impl App {
                         continue;
                     }
 
-                    let alpha = bitmap[py * metrics.width + px] as f32 / 255.0;
-                    if alpha == 0.0 {
+                    let coverage = bitmap[py * metrics.width + px] as f32 / 255.0;
+                    if coverage == 0.0 {
                         continue;
                     }
 
                     let idx = (dy as usize * buf_width as usize + dx as usize) * 4;
-                    let inv = 1.0 - alpha;
-                    buffer[idx] = (glyph_color.2 as f32 * alpha + buffer[idx] as f32 * inv) as u8;
-                    buffer[idx + 1] =
-                        (glyph_color.1 as f32 * alpha + buffer[idx + 1] as f32 * inv) as u8;
-                    buffer[idx + 2] =
-                        (glyph_color.0 as f32 * alpha + buffer[idx + 2] as f32 * inv) as u8;
+                    glyph_color.blend_over(&mut buffer[idx..idx + 3], coverage);
                 }
             }
 
@@ -610,31 +909,55 @@ impl App {
         0
     }
 
-    fn launch_selected(&mut self, results: &[FilteredApp], use_terminal: bool) -> Option<bool> {
+    /// Launch the selection, or the query if nothing matches. Returns whether
+    /// the window should close; failures keep it open to show the error.
+    fn launch_selected(&mut self, results: &[FilteredApp], mode: TerminalMode) -> bool {
         let selection = if let Some(app) = results.get(self.selected) {
             app.name.clone()
         } else if !self.query.trim().is_empty() {
             self.query.clone()
         } else {
-            return Some(true);
+            return true;
         };
 
-        // In filter mode, store result for printing at exit
+        // In filter mode, store result for printing at exit; Shift+Enter
+        // takes the typed text even when something matches, as in dmenu
+        if self.filter_mode && mode == TerminalMode::On {
+            self.filter_result = Some(self.query.clone());
+            return true;
+        }
         if self.filter_mode {
             self.filter_result = Some(selection);
-            return Some(true);
+            return true;
         }
 
-        let result = launcher::launch_command(&selection, use_terminal, &self.config);
-        if result.success && !result.command.is_empty() {
-            self.frequency.increment(&result.command);
-            let _ = self.frequency.save();
-        }
-        if result.needs_delay {
-            None // Signal to hide window and delay before exit
-        } else {
-            Some(true)
+        self.launch(selection, mode)
+    }
+
+    fn launch(&mut self, selection: String, mode: TerminalMode) -> bool {
+        let result = launcher::launch_command(&selection, mode, &self.config);
+        if result.success {
+            self.filter_result = Some(selection);
+            if !result.command.is_empty() {
+                self.frequency.increment(&result.command);
+                self.save_frequency();
+                if !self.apps.contains(&result.command) {
+                    self.apps.push(result.command);
+                }
+            }
+            return true;
         }
+        warn!(
+            "launch of '{}' failed: {}",
+            selection,
+            result.error.as_deref().unwrap_or("unknown error")
+        );
+        self.launch_error = Some(LaunchError {
+            selection,
+            message: result.error.unwrap_or_else(|| "unknown error".to_string()),
+            can_retry_in_terminal: !result.in_terminal,
+        });
+        false
     }
 
     fn insert_text(&mut self, text: &str) {
@@ -657,49 +980,47 @@ impl App {
     }
 
     fn handle_key(&mut self, keycode: u8, state: u16) -> KeyAction {
-        let results = filter_apps(&self.apps, &self.query, &self.frequency);
+        let results = self.results();
 
         let Some((keysym, ch)) = self.keymap.lookup(keycode, state) else {
             return KeyAction::None;
         };
-
-        let ctrl = (state & u16::from(KeyButMask::CONTROL)) != 0;
-        let shift = (state & u16::from(KeyButMask::SHIFT)) != 0;
-
-        if ctrl && (keysym == keysym::V_LOWER || keysym == keysym::V_UPPER) {
-            return KeyAction::Paste;
+        let action = self.bindings.action(keysym, state);
+        trace!(
+            "key: keysym {:#x} state {:#x} -> {:?}",
+            keysym,
+            state,
+            action
+        );
+        // Modifiers alone do nothing, so pressing Shift on the way to
+        // Shift+Enter keeps a launch error and its retry around
+        if keysym::is_modifier(keysym) {
+            return KeyAction::None;
         }
-        if ctrl && (keysym == keysym::Z_LOWER || keysym == keysym::Z_UPPER) {
-            if shift {
-                self.redo();
-            } else {
+        self.notice = None;
+
+        match action {
+            Some(Action::Paste) => return KeyAction::Paste,
+            Some(Action::Undo) => {
                 self.undo();
+                return KeyAction::None;
             }
-            return KeyAction::None;
-        }
-        if ctrl && (keysym == keysym::Y_LOWER || keysym == keysym::Y_UPPER) {
-            self.redo();
-            return KeyAction::None;
+            Some(Action::Redo) => {
+                self.redo();
+                return KeyAction::None;
+            }
+            _ => {}
         }
 
         if self.delete_confirm.is_some() {
-            match ch {
-                Some('y') | Some('Y') => {
-                    if let Some(name) = self.delete_confirm.take() {
-                        self.frequency.remove(&name);
-                        let _ = self.frequency.save();
-                    }
-                }
-                Some('n') | Some('N') => {
-                    self.delete_confirm = None;
-                }
-                _ if keysym == keysym::RETURN => {
+            match (ch, action) {
+                (Some('y') | Some('Y'), _) | (_, Some(Action::Accept)) => {
                     if let Some(name) = self.delete_confirm.take() {
                         self.frequency.remove(&name);
-                        let _ = self.frequency.save();
+                        self.save_frequency();
                     }
                 }
-                _ if keysym == keysym::ESCAPE => {
+                (Some('n') | Some('N'), _) | (_, Some(Action::Cancel)) => {
                     self.delete_confirm = None;
                 }
                 _ => {}
@@ -707,32 +1028,59 @@ impl App {
             return KeyAction::None;
         }
 
+        if let Some(error) = self.launch_error.take() {
+            match action {
+                Some(Action::Cancel) => return KeyAction::None,
+                Some(Action::AcceptInTerminal) if error.can_retry_in_terminal => {
+                    return if self.launch(error.selection, TerminalMode::On) {
+                        KeyAction::Quit
+                    } else {
+                        KeyAction::None
+                    };
+                }
+                // Any other key dismisses the error and is handled as usual
+                _ => {}
+            }
+        }
+
+        let Some(action) = action else {
+            if let Some(c) = ch {
+                let mut buf = [0; 4];
+                self.insert_text(c.encode_utf8(&mut buf));
+            }
+            return KeyAction::None;
+        };
+
+        let vertical = self.config.appearance.lines > 0;
         let cursor_at_end = self.cursor_pos >= self.query.chars().count();
 
-        match keysym {
-            keysym::ESCAPE => KeyAction::Quit,
-            keysym::RETURN | keysym::KP_ENTER => match self.launch_selected(&results, shift) {
-                Some(true) => KeyAction::Quit,
-                Some(false) => KeyAction::Quit,
-                None => KeyAction::QuitWithDelay,
-            },
-            keysym::TAB => {
+        match action {
+            Action::Cancel => return KeyAction::Quit,
+            Action::Accept | Action::AcceptInTerminal | Action::AcceptHold => {
+                let mode = match action {
+                    Action::AcceptHold => TerminalMode::Hold,
+                    Action::AcceptInTerminal => TerminalMode::On,
+                    _ => TerminalMode::Off,
+                };
+                if self.launch_selected(&results, mode) {
+                    return KeyAction::Quit;
+                }
+            }
+            Action::Complete => {
                 if let Some(app) = results.get(self.selected) {
                     self.query = app.name.clone();
                     self.cursor_pos = self.query.chars().count();
                     self.cursor_in_results = false;
                 }
-                KeyAction::None
             }
-            keysym::DELETE => {
+            Action::Forget => {
                 if let Some(app) = results.get(self.selected) {
                     if self.frequency.get(&app.name) > 0 {
                         self.delete_confirm = Some(app.name.clone());
                     }
                 }
-                KeyAction::None
             }
-            keysym::BACKSPACE => {
+            Action::DeleteBack => {
                 if self.cursor_pos > 0 {
                     self.save_undo();
                     let idx: usize = self
@@ -753,9 +1101,45 @@ impl App {
                     self.selected = 0;
                     self.scroll_offset = 0;
                 }
-                KeyAction::None
             }
-            keysym::LEFT => {
+            Action::Clear => {
+                if !self.query.is_empty() {
+                    self.save_undo();
+                    self.query.clear();
+                    self.cursor_pos = 0;
+                    self.cursor_in_results = false;
+                    self.selected = 0;
+                    self.scroll_offset = 0;
+                }
+            }
+            Action::LineStart => {
+                self.cursor_pos = 0;
+                self.cursor_in_results = false;
+            }
+            Action::LineEnd => {
+                self.cursor_pos = self.query.chars().count();
+                self.cursor_in_results = false;
+            }
+            // The horizontal bar is moved through with Left and Right only
+            Action::SelectNext if vertical => {
+                if self.selected + 1 < results.len() {
+                    self.selected += 1;
+                }
+            }
+            Action::SelectPrev if vertical => {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            Action::SelectNext | Action::SelectPrev => {}
+            // In the list the results are moved through with Up and Down
+            Action::MoveLeft if vertical => {
+                self.cursor_pos = self.cursor_pos.saturating_sub(1);
+            }
+            Action::MoveRight if vertical => {
+                if !cursor_at_end {
+                    self.cursor_pos += 1;
+                }
+            }
+            Action::MoveLeft => {
                 if self.cursor_in_results {
                     if self.selected > 1 {
                         let new_selected = self.selected - 1;
@@ -775,9 +1159,8 @@ impl App {
                 } else if self.cursor_pos > 0 {
                     self.cursor_pos -= 1;
                 }
-                KeyAction::None
             }
-            keysym::RIGHT => {
+            Action::MoveRight => {
                 if self.cursor_in_results {
                     if self.selected < self.last_visible {
                         self.selected += 1;
@@ -791,33 +1174,18 @@ impl App {
                 } else if self.cursor_pos < self.query.chars().count() {
                     self.cursor_pos += 1;
                 }
-                KeyAction::None
-            }
-            _ => {
-                if let Some(c) = ch {
-                    if !c.is_control() {
-                        self.save_undo();
-                        let idx: usize = self
-                            .query
-                            .chars()
-                            .take(self.cursor_pos)
-                            .map(|c| c.len_utf8())
-                            .sum();
-                        self.query.insert(idx, c);
-                        self.cursor_pos += 1;
-                        self.cursor_in_results = false;
-                        self.selected = 0;
-                        self.scroll_offset = 0;
-                    }
-                }
-                KeyAction::None
             }
+            Action::Paste | Action::Undo | Action::Redo => {}
         }
+        KeyAction::None
     }
 }
 
 struct X11Context {
     conn: x11rb::rust_connection::RustConnection,
+    root: u32,
+    screen_width: u16,
+    screen_height: u16,
     win_id: u32,
     gc_id: u32,
     depth: u8,
@@ -826,6 +1194,70 @@ struct X11Context {
 }
 
 impl X11Context {
+    /// Grab the keyboard, retrying while another client still holds it.
+    fn grab_keyboard(&self, attempts: u32) -> Result<bool, Box<dyn std::error::Error>> {
+        for attempt in 0..attempts {
+            let reply = self
+                .conn
+                .grab_keyboard(
+                    false,
+                    self.win_id,
+                    x11rb::CURRENT_TIME,
+                    GrabMode::ASYNC,
+                    GrabMode::ASYNC,
+                )?
+                .reply()?;
+            if reply.status == GrabStatus::SUCCESS {
+                return Ok(true);
+            }
+            if attempt + 1 == attempts {
+                warn!(
+                    "keyboard grab failed after {} attempts (status {:?})",
+                    attempts, reply.status
+                );
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        Ok(false)
+    }
+
+    /// Move the window onto the monitor with the focused window and give it
+    /// `current_height`. Returns the new width.
+    fn place(&mut self, position: &str) -> Result<u16, Box<dyn std::error::Error>> {
+        let monitor =
+            get_active_monitor(&self.conn, self.root, self.screen_width, self.screen_height);
+        let win_y = window_y(position, &monitor, self.current_height);
+        self.conn.configure_window(
+            self.win_id,
+            &ConfigureWindowAux::new()
+                .x(monitor.x as i32)
+                .y(win_y as i32)
+                .width(monitor.width as u32)
+                .height(self.current_height as u32),
+        )?;
+        self.current_width = monitor.width;
+        Ok(monitor.width)
+    }
+
+    /// Bring the window back on top and take the keyboard again.
+    fn raise(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.conn.configure_window(
+            self.win_id,
+            &ConfigureWindowAux::new().stack_mode(StackMode::ABOVE),
+        )?;
+        self.conn
+            .set_input_focus(InputFocus::PARENT, self.win_id, x11rb::CURRENT_TIME)?;
+        self.conn.grab_keyboard(
+            false,
+            self.win_id,
+            x11rb::CURRENT_TIME,
+            GrabMode::ASYNC,
+            GrabMode::ASYNC,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
     fn redraw(&self, pixels: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
         let pixmap_id = self.conn.generate_id()?;
         self.conn.create_pixmap(
@@ -866,10 +1298,193 @@ impl X11Context {
     }
 }
 
+/// Map the window, draw the first frame and take the keyboard.
+fn show(ctx: &mut X11Context, app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
+    ctx.conn.map_window(ctx.win_id)?;
+    ctx.conn.flush()?;
+
+    let pixels = app.render(ctx.current_width, ctx.current_height);
+    ctx.redraw(&pixels)?;
+
+    if !ctx.grab_keyboard(100)? {
+        let _ = ctx
+            .conn
+            .set_input_focus(InputFocus::PARENT, ctx.win_id, x11rb::CURRENT_TIME);
+        ctx.conn.flush()?;
+    }
+    Ok(())
+}
+
+/// Apply an edited config file, or keep the current one and say why.
+/// Watch the files `config` was loaded from. The user's config may not exist
+/// yet, and is watched for being created.
+fn watch_config(config: &Config) -> Option<FileWatcher> {
+    let mut watched = config.sources.clone();
+    watched.push(config::config_path());
+    FileWatcher::new(&watched)
+        .inspect_err(|e| debug!("not watching the config files: {}", e))
+        .ok()
+}
+
+fn reload_config(
+    ctx: &mut X11Context,
+    app: &mut App,
+    visible: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match app.reload_config(Config::load()) {
+        Ok(()) => {
+            info!("config reloaded");
+            app.notice = None;
+            ctx.current_height = app.window_height();
+            if visible {
+                app.screen_width = ctx.place(&app.config.appearance.position)?;
+            }
+        }
+        Err(e) => {
+            warn!("config not reloaded: {}", e);
+            app.notice = Some(format!("Config not reloaded: {}", e));
+        }
+    }
+    if visible {
+        let pixels = app.render(ctx.current_width, ctx.current_height);
+        ctx.redraw(&pixels)?;
+    }
+    Ok(())
+}
+
+/// Show a hidden daemon window on the current monitor with fresh apps.
+fn unhide(
+    ctx: &mut X11Context,
+    app: &mut App,
+    last_refresh: &mut Instant,
+) -> Result<(), Box<dyn std::error::Error>> {
+    app.refresh_apps(last_refresh);
+    app.screen_width = ctx.place(&app.config.appearance.position)?;
+    show(ctx, app)
+}
+
+/// Unmap the window and forget the query, keeping everything else warm.
+fn hide(ctx: &X11Context, app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
+    ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
+    ctx.conn.unmap_window(ctx.win_id)?;
+    ctx.conn.flush()?;
+    app.reset();
+    Ok(())
+}
+
+/// What to do with a client after its request, plain word or JSON.
+enum MessageAction {
+    Reply(String),
+    /// Keep the connection and answer once the window closes
+    Await,
+    Close,
+}
+
+fn handle_message(
+    ctx: &mut X11Context,
+    app: &mut App,
+    visible: &mut bool,
+    last_refresh: &mut Instant,
+    line: &str,
+) -> Result<MessageAction, Box<dyn std::error::Error>> {
+    match line {
+        lock::REQUEST_PING => return Ok(MessageAction::Reply(remote::ok())),
+        // A hidden daemon shows itself whatever was asked
+        lock::REQUEST_CLOSE | lock::REQUEST_FOCUS if !*visible => {
+            unhide(ctx, app, last_refresh)?;
+            *visible = true;
+            return Ok(MessageAction::Reply(remote::ok()));
+        }
+        lock::REQUEST_CLOSE => return Ok(MessageAction::Close),
+        lock::REQUEST_FOCUS => {
+            ctx.raise()?;
+            return Ok(MessageAction::Reply(remote::ok()));
+        }
+        _ if !remote::is_message(line) => {
+            warn!("unknown instance request '{}'", line);
+            return Ok(MessageAction::Reply(remote::error("unknown request")));
+        }
+        _ => {}
+    }
+    let request = match remote::parse(line) {
+        Ok(request) => request,
+        Err(e) => {
+            warn!("invalid message: {}", e);
+            return Ok(MessageAction::Reply(remote::error(&e)));
+        }
+    };
+    debug!("message: {:?}", request);
+    match request {
+        Request::Show if *visible => ctx.raise()?,
+        Request::Show => {
+            unhide(ctx, app, last_refresh)?;
+            *visible = true;
+        }
+        Request::Hide if *visible => return Ok(MessageAction::Close),
+        Request::Hide => {}
+        Request::SetQuery { query } => app.set_query(query),
+        Request::SetItems { items } => app.set_items(items),
+        Request::GetState => {
+            return Ok(MessageAction::Reply(remote::state(&app.state(*visible))));
+        }
+        Request::AwaitSelection => return Ok(MessageAction::Await),
+    }
+    if *visible {
+        let pixels = app.render(ctx.current_width, ctx.current_height);
+        ctx.redraw(&pixels)?;
+    }
+    Ok(MessageAction::Reply(remote::ok()))
+}
+
+fn reply_selection(awaiting: &mut Vec<UnixStream>, selection: Option<&str>) {
+    let reply = remote::selection(selection);
+    for stream in awaiting.drain(..) {
+        lock::reply(stream, &reply);
+    }
+}
+
+/// Block until at least one of `fds` is readable.
+fn wait_readable(fds: &[i32]) -> std::io::Result<Vec<bool>> {
+    let mut pollfds: Vec<libc::pollfd> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    loop {
+        let ret = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+        if ret >= 0 {
+            break;
+        }
+        let err = std::io::Error::last_os_error();
+        if err.kind() != std::io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(pollfds.iter().map(|p| p.revents != 0).collect())
+}
+
+fn window_y(position: &str, monitor: &MonitorGeometry, window_height: u16) -> i16 {
+    match position {
+        "bottom" => monitor.y + monitor.height as i16 - window_height as i16,
+        "center" => monitor.y + (monitor.height as i16 - window_height as i16) / 2,
+        _ => monitor.y, // "top" (default)
+    }
+}
+
 fn is_xwayland() -> bool {
     std::env::var("WAYLAND_DISPLAY").is_ok()
 }
 
+/// Height of the bar, plus one row per line of the vertical list.
+fn window_height(config: &Config, font: &Font) -> u16 {
+    let dpi_scale = config.appearance.dpi as f32 / 72.0;
+    let font_size = config.appearance.font_size as f32 * dpi_scale;
+    compute_window_height(font, font_size) * (config.appearance.lines + 1)
+}
+
 fn compute_window_height(font: &Font, font_size: f32) -> u16 {
     // Upper padding of 2px and lower padding of 2px
     if let Some(metrics) = font.horizontal_line_metrics(font_size) {
@@ -954,6 +1569,10 @@ fn get_active_monitor(
         let ch = info.height as i32;
 
         if focus_x >= cx && focus_x < cx + cw && focus_y >= cy && focus_y < cy + ch {
+            debug!(
+                "monitor {}x{}+{}+{} has the focused window",
+                info.width, info.height, info.x, info.y
+            );
             return MonitorGeometry {
                 x: info.x,
                 y: info.y,
@@ -981,32 +1600,28 @@ pub fn run(
     frequency: Frequency,
     apps: Vec<String>,
     filter_mode: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
+    listener: Option<UnixListener>,
+    daemon: bool,
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
     let (conn, screen_num) = x11rb::connect(None)?;
     let setup = conn.setup();
     let screen = &setup.roots[screen_num];
     let root = screen.root;
+    let (screen_width, screen_height) = (screen.width_in_pixels, screen.height_in_pixels);
     let depth = screen.root_depth;
     let visual = screen.root_visual;
 
     let monitor = get_active_monitor(&conn, root, screen.width_in_pixels, screen.height_in_pixels);
     let mon_x = monitor.x;
-    let mon_y = monitor.y;
     let mon_width = monitor.width;
-    let mon_height = monitor.height;
 
-    let dpi_scale = config.appearance.dpi as f32 / 72.0;
+    let font = load_font(&config.appearance.font_family).ok_or_else(|| {
+        error!("font '{}' not found", config.appearance.font_family);
+        format!("Font '{}' not found", config.appearance.font_family)
+    })?;
+    let window_height = window_height(&config, &font);
 
-    let font = load_font(&config.appearance.font_family)
-        .unwrap_or_else(|| panic!("Font '{}' not found", config.appearance.font_family));
-    let font_size = config.appearance.font_size as f32 * dpi_scale;
-    let window_height = compute_window_height(&font, font_size);
-
-    let win_y: i16 = match config.appearance.position.as_str() {
-        "bottom" => mon_y + mon_height as i16 - window_height as i16,
-        "center" => mon_y + (mon_height as i16 - window_height as i16) / 2,
-        _ => mon_y, // "top" (default)
-    };
+    let win_y = window_y(&config.appearance.position, &monitor, window_height);
 
     let keymap = KeyboardMap::new(&conn, setup)?;
 
@@ -1265,6 +1880,9 @@ pub fn run(
 
     let mut ctx = X11Context {
         conn,
+        root,
+        screen_width,
+        screen_height,
         win_id,
         gc_id,
         depth,
@@ -1272,48 +1890,87 @@ pub fn run(
         current_height: window_height,
     };
 
-    ctx.conn.map_window(win_id)?;
-    ctx.conn.flush()?;
-
-    let pixels = app.render(ctx.current_width, ctx.current_height);
-    ctx.redraw(&pixels)?;
-
-    let mut grabbed = false;
-    for attempt in 0..100 {
-        let reply = ctx
-            .conn
-            .grab_keyboard(
-                false,
-                win_id,
-                x11rb::CURRENT_TIME,
-                GrabMode::ASYNC,
-                GrabMode::ASYNC,
-            )?
-            .reply()?;
-        if reply.status == GrabStatus::SUCCESS {
-            grabbed = true;
-            break;
-        }
-        if attempt == 99 {
-            eprintln!(
-                "warn: keyboard grab failed after 100 attempts (status {:?})",
-                reply.status
-            );
-        }
-        std::thread::sleep(std::time::Duration::from_millis(10));
-    }
-
-    if !grabbed {
-        let _ = ctx
-            .conn
-            .set_input_focus(InputFocus::PARENT, win_id, x11rb::CURRENT_TIME);
-        ctx.conn.flush()?;
+    let mut visible = false;
+    let mut last_refresh = Instant::now();
+    if daemon {
+        info!("daemon ready, waiting for show requests");
+    } else {
+        show(&mut ctx, &mut app)?;
+        visible = true;
     }
 
     let mut paste_pending = false;
-
-    loop {
-        let event = ctx.conn.wait_for_event()?;
+    let x_fd = ctx.conn.stream().as_raw_fd();
+
+    let mut watcher = watch_config(&app.config);
+    // await-selection clients, answered when the window closes
+    let mut awaiting: Vec<UnixStream> = Vec::new();
+    // Clients still sending their request, read as it arrives
+    let mut connections: Vec<lock::Connection> = Vec::new();
+
+    'events: loop {
+        let event = match ctx.conn.poll_for_event()? {
+            Some(event) => event,
+            None => {
+                ctx.conn.flush()?;
+                let mut close = false;
+                let mut fds = vec![x_fd];
+                let listener_idx = listener.as_ref().map(|l| {
+                    fds.push(l.as_raw_fd());
+                    fds.len() - 1
+                });
+                let watcher_idx = watcher.as_ref().map(|w| {
+                    fds.push(w.as_raw_fd());
+                    fds.len() - 1
+                });
+                fds.extend(connections.iter().map(|c| c.as_raw_fd()));
+                let ready = wait_readable(&fds)?;
+                let is_ready = |idx: Option<usize>| idx.is_some_and(|i| ready[i]);
+
+                let changed =
+                    is_ready(watcher_idx) && watcher.as_ref().is_some_and(|w| w.changed());
+                if changed {
+                    reload_config(&mut ctx, &mut app, visible)?;
+                    // Includes may have come or gone
+                    watcher = watch_config(&app.config);
+                }
+                if let (Some(listener), true) = (&listener, is_ready(listener_idx)) {
+                    lock::accept(listener, &mut connections);
+                }
+                for (stream, request) in lock::take_requests(&mut connections) {
+                    debug!("instance request '{}'", request);
+                    let action = handle_message(
+                        &mut ctx,
+                        &mut app,
+                        &mut visible,
+                        &mut last_refresh,
+                        &request,
+                    );
+                    match action {
+                        Ok(MessageAction::Reply(reply)) => lock::reply(stream, &reply),
+                        Ok(MessageAction::Await) => awaiting.push(stream),
+                        Ok(MessageAction::Close) => {
+                            lock::reply(stream, &remote::ok());
+                            close = true;
+                        }
+                        // One bad request must not take a daemon down
+                        Err(e) => {
+                            warn!("request '{}' failed: {}", request, e);
+                            lock::reply(stream, &remote::error(&e.to_string()));
+                        }
+                    }
+                }
+                if close {
+                    reply_selection(&mut awaiting, app.filter_result.as_deref());
+                    if !daemon {
+                        break 'events;
+                    }
+                    hide(&ctx, &mut app)?;
+                    visible = false;
+                }
+                continue;
+            }
+        };
 
         match event {
             Event::ConfigureNotify(e) => {
@@ -1333,12 +1990,14 @@ pub fn run(
             Event::Expose(_) => {}
             Event::KeyPress(e) => {
                 match app.handle_key(e.detail, e.state.into()) {
-                    KeyAction::Quit => break,
-                    KeyAction::QuitWithDelay => {
-                        ctx.conn.unmap_window(ctx.win_id)?;
-                        ctx.conn.flush()?;
-                        std::thread::sleep(std::time::Duration::from_millis(500));
-                        break;
+                    KeyAction::Quit => {
+                        reply_selection(&mut awaiting, app.filter_result.as_deref());
+                        if !daemon {
+                            break;
+                        }
+                        hide(&ctx, &mut app)?;
+                        visible = false;
+                        continue;
                     }
                     KeyAction::Paste => {
                         ctx.conn.convert_selection(
@@ -1357,8 +2016,9 @@ pub fn run(
                 let pixels = app.render(ctx.current_width, ctx.current_height);
                 ctx.redraw(&pixels)?;
             }
+            #[allow(clippy::collapsible_match)]
             Event::SelectionNotify(e) => {
-                if paste_pending && e.property != AtomEnum::NONE.into() {
+                if paste_pending && e.property != u32::from(AtomEnum::NONE) {
                     paste_pending = false;
                     if let Ok(reply) = ctx.conn.get_property(
                         true,
@@ -1378,23 +2038,8 @@ pub fn run(
                     }
                 }
             }
-            Event::FocusOut(_) => {
-                for _ in 0..50 {
-                    let reply = ctx
-                        .conn
-                        .grab_keyboard(
-                            false,
-                            ctx.win_id,
-                            x11rb::CURRENT_TIME,
-                            GrabMode::ASYNC,
-                            GrabMode::ASYNC,
-                        )?
-                        .reply()?;
-                    if reply.status == GrabStatus::SUCCESS {
-                        break;
-                    }
-                    std::thread::sleep(std::time::Duration::from_millis(10));
-                }
+            Event::FocusOut(_) if visible => {
+                ctx.grab_keyboard(50)?;
             }
             _ => {}
         }
@@ -1403,9 +2048,5 @@ pub fn run(
     ctx.conn.ungrab_keyboard(x11rb::CURRENT_TIME)?;
     ctx.conn.flush()?;
 
-    if let Some(selection) = app.filter_result {
-        println!("{}", selection);
-    }
-
-    Ok(())
+    Ok(app.filter_result)
 }
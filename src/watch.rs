@@ -0,0 +1,101 @@
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Watches directories for files being written or replaced, using inotify.
+/// The directory is watched rather than the file so that editors which save
+/// by renaming a new file over the old one are noticed too.
+pub struct FileWatcher {
+    fd: OwnedFd,
+    /// Watch descriptor of the directory and name of each file
+    files: Vec<(i32, Vec<u8>)>,
+}
+
+impl FileWatcher {
+    /// Watch `paths`. Files whose directory can't be watched are skipped;
+    /// it is an error only if none can be.
+    pub fn new(paths: &[PathBuf]) -> io::Result<Self> {
+        let raw = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if raw < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(raw) };
+
+        let mut files = Vec::new();
+        let mut error = io::Error::new(io::ErrorKind::InvalidInput, "no file to watch");
+        for path in paths {
+            match Self::add_watch(raw, path) {
+                Ok(wd) => {
+                    files.push((wd, path.file_name().unwrap_or_default().as_bytes().to_vec()))
+                }
+                Err(e) => error = e,
+            }
+        }
+        if files.is_empty() {
+            return Err(error);
+        }
+        Ok(Self { fd, files })
+    }
+
+    fn add_watch(raw: RawFd, path: &Path) -> io::Result<i32> {
+        let (Some(dir), Some(_)) = (path.parent(), path.file_name()) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not a file path",
+            ));
+        };
+        let dir = CString::new(dir.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul byte"))?;
+        // Not IN_CREATE: a new file is still empty then, and is reported
+        // again once written
+        let mask = libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO | libc::IN_DELETE;
+        let wd = unsafe { libc::inotify_add_watch(raw, dir.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(wd)
+    }
+
+    /// Drain pending events and tell whether any concerned one of the files.
+    pub fn changed(&self) -> bool {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut buf = [0u8; 4096];
+        let mut changed = false;
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if n <= 0 {
+                return changed;
+            }
+            let mut offset = 0;
+            while offset + HEADER <= n as usize {
+                let event: libc::inotify_event =
+                    unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr() as *const _) };
+                let name = &buf[offset + HEADER..offset + HEADER + event.len as usize];
+                // The name is padded with nul bytes
+                let name = name.split(|&b| b == 0).next().unwrap_or(&[]);
+                if self
+                    .files
+                    .iter()
+                    .any(|(wd, file)| *wd == event.wd && name == file.as_slice())
+                {
+                    changed = true;
+                }
+                offset += HEADER + event.len as usize;
+            }
+        }
+    }
+}
+
+impl AsRawFd for FileWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
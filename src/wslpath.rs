@@ -0,0 +1,62 @@
+use std::env;
+use std::path::{Component, Path};
+
+/// Translate an absolute Linux path into the form Windows programs expect,
+/// the way `wslpath -w` does: `/mnt/c/Users` becomes `C:\Users`, anything
+/// else goes through the `\\wsl.localhost\<distro>` share. `.` and `..`
+/// are resolved first.
+pub fn to_windows(path: &Path) -> Option<String> {
+    if !path.is_absolute() {
+        return None;
+    }
+    // `..` is resolved lexically, like `realpath -s`; `..` of `/` is `/`
+    let mut parts: Vec<String> = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(s) => parts.push(s.to_string_lossy().into_owned()),
+            Component::ParentDir => {
+                parts.pop();
+            }
+            _ => {}
+        }
+    }
+
+    if parts.len() >= 2 && parts[0] == "mnt" {
+        let drive = &parts[1];
+        if drive.len() == 1 && drive.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Some(format!(
+                "{}:\\{}",
+                drive.to_ascii_uppercase(),
+                parts[2..].join("\\")
+            ));
+        }
+    }
+
+    let distro = env::var("WSL_DISTRO_NAME").ok().filter(|d| !d.is_empty())?;
+    Some(format!(
+        "\\\\wsl.localhost\\{}\\{}",
+        distro,
+        parts.join("\\")
+    ))
+}
+
+/// Translate a command line argument if it is an absolute path to an
+/// existing file or directory, `~` having been expanded already. Anything
+/// else is left alone.
+pub fn translate_arg(arg: &str) -> Option<String> {
+    if !arg.starts_with('/') {
+        return None;
+    }
+    let path = Path::new(arg);
+    if !path.exists() {
+        return None;
+    }
+    to_windows(path)
+}
+
+/// Whether we are running inside WSL with Windows interop available.
+pub fn is_wsl() -> bool {
+    env::var_os("WSL_DISTRO_NAME").is_some()
+        || env::var_os("WSL_INTEROP").is_some()
+        || Path::new("/proc/sys/fs/binfmt_misc/WSLInterop").exists()
+}